@@ -142,6 +142,41 @@ impl<T> Arc<T> {
         unsafe { &**self._ptr }
     }
 
+    /// Returns the contained value if this is the only strong pointer. Otherwise the
+    /// `Arc` is returned unchanged. `ArcTrait` objects created from this `Arc` count as
+    /// strong pointers.
+    pub fn try_unwrap(self) -> Result<T, Arc<T>> {
+        // See the Drop impl for the memory orderings.
+        if self.inner().strong.compare_and_swap(1, 0, Release) != 1 {
+            return Err(self);
+        }
+
+        atomic::fence(Acquire);
+
+        unsafe {
+            let data = ptr::read(&self.inner().data);
+            // The strong pointers collectively hold one weak pointer. Dropping it frees
+            // the allocation unless there are other weak pointers around.
+            let weak = Weak { _ptr: self._ptr };
+            mem::forget(self);
+            drop(weak);
+            Ok(data)
+        }
+    }
+
+    /// Returns a mutable reference to the contained value if there are no other strong
+    /// or weak pointers.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // The strong pointers collectively hold one weak pointer.
+        let inner = self.inner();
+        if inner.strong.load(Acquire) != 1 || inner.weak.load(Acquire) != 1 {
+            return None;
+        }
+        // Nobody else can access the data and we're borrowed mutably so no new pointers
+        // can be created.
+        unsafe { Some(&mut (**self._ptr).data) }
+    }
+
     /// Creates an ArcTrait from an Arc. `t` must be a trait object created by calling
     /// `&*self as &Trait`. Otherwise the behavior is undefined.
     pub unsafe fn as_trait<Trait: ?Sized>(&self, t: &Trait) -> ArcTrait<Trait> {
@@ -178,6 +213,11 @@ impl<T> Arc<T> {
     pub fn unique_id(&self) -> usize {
         *self._ptr as usize
     }
+
+    /// Returns whether both `Arc` objects point to the same allocation.
+    pub fn ptr_eq(this: &Arc<T>, other: &Arc<T>) -> bool {
+        *this._ptr == *other._ptr
+    }
 }
 
 impl<T> Clone for Arc<T> {
@@ -337,6 +377,38 @@ impl<Trait: ?Sized> ArcTrait<Trait> {
     pub fn unique_id(&self) -> usize {
         *self._ptr as usize
     }
+
+    /// Returns whether both `ArcTrait` objects point to the same allocation.
+    pub fn ptr_eq(this: &ArcTrait<Trait>, other: &ArcTrait<Trait>) -> bool {
+        *this._ptr == *other._ptr
+    }
+
+    /// Converts the `ArcTrait` back into the `Arc` it was created from. The new `Arc`
+    /// shares the reference counts with all other pointers to the allocation. If the
+    /// size, alignment, or destructor don't match those of `T`, the `ArcTrait` is
+    /// returned unchanged.
+    ///
+    /// This is unsafe because the check is only a heuristic: Two types with the same
+    /// layout and identical drop glue can be indistinguishable. The caller has to ensure
+    /// that the object was created from an `Arc<T>`.
+    ///
+    /// The check can also fail for the right type: The destructor of `T` can be
+    /// instantiated more than once, e.g., once in each crate that uses it, and the
+    /// `ArcTrait` might have been created with a different instance than the one this
+    /// function compares against. An `Err` therefore doesn't prove that the object isn't
+    /// a `T`.
+    pub unsafe fn downcast<T>(self) -> Result<Arc<T>, ArcTrait<Trait>> {
+        let destructor: fn(*mut ()) = ptr_drop::<T>;
+        if self._size != size_of::<ArcInner<T>>()
+                || self._alignment != align_of::<ArcInner<T>>()
+                || self._destructor as usize != destructor as usize {
+            return Err(self);
+        }
+        // The strong reference held by `self` is transferred to the `Arc`.
+        let arc = Arc { _ptr: mem::transmute(self._ptr) };
+        mem::forget(self);
+        Ok(arc)
+    }
 }
 
 impl<Trait: ?Sized> Clone for ArcTrait<Trait> {
@@ -476,7 +548,10 @@ impl<Trait: ?Sized> Drop for WeakTrait<Trait> {
 
 #[cfg(test)]
 mod test {
-    use super::{Arc, ArcTrait};
+    use std::rc::{Rc};
+    use std::cell::{Cell};
+
+    use super::{Arc, ArcTrait, Weak, WeakTrait};
 
     struct X {
         x: u8
@@ -524,4 +599,217 @@ mod test {
         drop(arc_trait);
         assert!(weak.upgrade().is_none());
     }
+
+    struct D(Rc<Cell<usize>>);
+
+    impl Drop for D {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    impl Y for D {
+        fn f(&self) -> u8 {
+            4
+        }
+    }
+
+    enum Handle {
+        Arc(Arc<D>),
+        ArcTrait(ArcTrait<Y>),
+        Weak(Weak<D>),
+        WeakTrait(WeakTrait<Y>),
+    }
+
+    impl Handle {
+        fn is_strong(&self) -> bool {
+            match *self {
+                Handle::Arc(..) | Handle::ArcTrait(..) => true,
+                _ => false,
+            }
+        }
+
+        fn counts(&self) -> (usize, usize) {
+            match *self {
+                Handle::Arc(ref a) => (a.strong_count(), a.weak_count()),
+                Handle::ArcTrait(ref a) => (a.strong_count(), a.weak_count()),
+                Handle::Weak(ref w) => (w.strong_count(), w.weak_count()),
+                Handle::WeakTrait(ref w) => (w.strong_count(), w.weak_count()),
+            }
+        }
+
+        fn upgrades(&self) -> bool {
+            match *self {
+                Handle::Arc(..) | Handle::ArcTrait(..) => true,
+                Handle::Weak(ref w) => w.upgrade().is_some(),
+                Handle::WeakTrait(ref w) => w.upgrade().is_some(),
+            }
+        }
+    }
+
+    fn permutations(n: usize) -> Vec<Vec<usize>> {
+        if n == 0 {
+            return vec!(vec!());
+        }
+        let mut rv = vec!();
+        for perm in permutations(n - 1) {
+            for i in 0..n {
+                let mut perm = perm.clone();
+                perm.insert(i, n - 1);
+                rv.push(perm);
+            }
+        }
+        rv
+    }
+
+    #[test]
+    fn mixed_drop_order() {
+        for perm in permutations(4) {
+            let drops = Rc::new(Cell::new(0));
+
+            let arc = Arc::new(D(drops.clone()));
+            let arc_trait: ArcTrait<Y> = unsafe { arc.as_trait(&*arc as &(Y+'static)) };
+            let weak = arc.downgrade();
+            let weak_trait = arc_trait.downgrade();
+            assert_eq!(arc_trait.f(), 4);
+
+            let mut handles = vec!(Some(Handle::Arc(arc)),
+                                   Some(Handle::ArcTrait(arc_trait)),
+                                   Some(Handle::Weak(weak)),
+                                   Some(Handle::WeakTrait(weak_trait)));
+            let mut strong = 2;
+            let mut weak = 2;
+
+            for &i in &perm {
+                let handle = handles[i].take().unwrap();
+                if handle.is_strong() {
+                    strong -= 1;
+                } else {
+                    weak -= 1;
+                }
+                drop(handle);
+
+                // The destructor runs exactly once when the last strong pointer goes
+                // away, no matter which kind of pointer it is.
+                assert_eq!(drops.get(), if strong == 0 { 1 } else { 0 });
+
+                // The strong pointers collectively hold one weak reference. The
+                // allocation is freed once the weak count drops to zero.
+                for handle in handles.iter().filter_map(|h| h.as_ref()) {
+                    let implicit = if strong > 0 { 1 } else { 0 };
+                    assert_eq!(handle.counts(), (strong, weak + implicit - 1));
+                    assert_eq!(handle.upgrades(), strong > 0);
+                }
+            }
+
+            assert_eq!(drops.get(), 1);
+        }
+    }
+
+    #[test]
+    fn try_unwrap() {
+        let drops = Rc::new(Cell::new(0));
+        let arc = Arc::new(D(drops.clone()));
+        let arc2 = arc.clone();
+        let arc = match arc.try_unwrap() {
+            Err(arc) => arc,
+            _ => panic!(),
+        };
+        drop(arc2);
+        let weak = arc.downgrade();
+        let d = match arc.try_unwrap() {
+            Ok(d) => d,
+            _ => panic!(),
+        };
+        assert_eq!(drops.get(), 0);
+        assert!(weak.upgrade().is_none());
+        drop(d);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut arc = Arc::new(X { x: 3 });
+        arc.get_mut().unwrap().x = 4;
+        assert_eq!(arc.x, 4);
+
+        let weak = arc.downgrade();
+        assert!(arc.get_mut().is_none());
+        drop(weak);
+
+        let arc2 = arc.clone();
+        assert!(arc.get_mut().is_none());
+        drop(arc2);
+
+        assert!(arc.get_mut().is_some());
+    }
+
+    #[test]
+    fn ptr_eq() {
+        let arc = Arc::new(X { x: 3 });
+        let arc2 = arc.clone();
+        let arc3 = Arc::new(X { x: 3 });
+        assert!(Arc::ptr_eq(&arc, &arc2));
+        assert!(!Arc::ptr_eq(&arc, &arc3));
+
+        let (t, t2, t3): (ArcTrait<Y>, ArcTrait<Y>, ArcTrait<Y>) = unsafe {
+            (arc.as_trait(&*arc as &(Y+'static)),
+             arc2.as_trait(&*arc2 as &(Y+'static)),
+             arc3.as_trait(&*arc3 as &(Y+'static)))
+        };
+        assert!(ArcTrait::ptr_eq(&t, &t2));
+        assert!(!ArcTrait::ptr_eq(&t, &t3));
+    }
+
+    struct Z {
+        z: u64,
+    }
+
+    impl Y for Z {
+        fn f(&self) -> u8 {
+            self.z as u8
+        }
+    }
+
+    #[test]
+    fn downcast() {
+        let drops = Rc::new(Cell::new(0));
+        let arc = Arc::new(D(drops.clone()));
+        let arc_trait: ArcTrait<Y> = unsafe { arc.as_trait(&*arc as &(Y+'static)) };
+        let weak = arc.downgrade();
+        drop(arc);
+
+        let arc = match unsafe { arc_trait.downcast::<D>() } {
+            Ok(arc) => arc,
+            _ => panic!(),
+        };
+        assert_eq!(arc.strong_count(), 1);
+        assert_eq!(arc.weak_count(), 1);
+        assert!(Arc::ptr_eq(&arc, &weak.upgrade().unwrap()));
+        drop(arc);
+        assert_eq!(drops.get(), 1);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn downcast_mismatch() {
+        let arc = Arc::new(Z { z: 5 });
+        let arc_trait: ArcTrait<Y> = unsafe { arc.as_trait(&*arc as &(Y+'static)) };
+
+        // A type with a different size. Types of the same size and alignment can only be
+        // told apart by their drop glue which might be shared.
+        let arc_trait = match unsafe { arc_trait.downcast::<[u64; 4]>() } {
+            Err(arc_trait) => arc_trait,
+            _ => panic!(),
+        };
+        assert_eq!(arc_trait.f(), 5);
+        assert_eq!(arc.strong_count(), 2);
+
+        let arc2 = match unsafe { arc_trait.downcast::<Z>() } {
+            Ok(arc2) => arc2,
+            _ => panic!(),
+        };
+        assert!(Arc::ptr_eq(&arc, &arc2));
+        assert_eq!(arc.strong_count(), 2);
+    }
 }
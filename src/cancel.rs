@@ -0,0 +1,71 @@
+//! Tokens for cancelling blocking receive operations.
+
+use std::sync::atomic::{AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst};
+use std::{fmt};
+
+use arc::{Arc};
+
+/// A token that cancels the receive operations it's passed to.
+///
+/// Tokens are created by the consumers that support cancellation, e.g.,
+/// `mpsc::unbounded::Consumer::cancel_token`, and can only be used with the consumer
+/// that created them. Clones of a token share the same state.
+pub struct CancelToken<'a> {
+    data: Arc<Inner<'a>>,
+}
+
+struct Inner<'a> {
+    // The id of the channel the token belongs to.
+    id: usize,
+    cancelled: AtomicBool,
+    // Wakes up the receivers sleeping in the channel.
+    wake: Box<Fn()+Send+Sync+'a>,
+}
+
+impl<'a> CancelToken<'a> {
+    /// Creates a new token for the channel with id `id`. `wake` is called after the token
+    /// has been cancelled and has to wake up all sleeping receivers of the channel.
+    pub fn new<F>(id: usize, wake: F) -> CancelToken<'a>
+        where F: Fn()+Send+Sync+'a,
+    {
+        CancelToken {
+            data: Arc::new(Inner {
+                id: id,
+                cancelled: AtomicBool::new(false),
+                wake: Box::new(wake),
+            }),
+        }
+    }
+
+    /// Cancels all current and future receive operations that use this token.
+    pub fn cancel(&self) {
+        self.data.cancelled.store(true, SeqCst);
+        (self.data.wake)();
+    }
+
+    /// Returns whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.data.cancelled.load(SeqCst)
+    }
+
+    /// Returns the id of the channel the token belongs to.
+    pub fn id(&self) -> usize {
+        self.data.id
+    }
+}
+
+impl<'a> Clone for CancelToken<'a> {
+    fn clone(&self) -> CancelToken<'a> {
+        CancelToken { data: self.data.clone() }
+    }
+}
+
+impl<'a> fmt::Debug for CancelToken<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelToken")
+         .field("id", &self.data.id)
+         .field("cancelled", &self.is_cancelled())
+         .finish()
+    }
+}
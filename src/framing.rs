@@ -0,0 +1,121 @@
+//! Reassembling length-prefixed frames from byte chunks sent over a channel.
+//!
+//! Every frame starts with the length of its payload as a big-endian `u32` followed by
+//! the payload itself. The chunks the frames are sent in don't have to respect frame
+//! boundaries: a frame can be split across several chunks and a chunk can contain
+//! several frames.
+
+use std::cell::{RefCell};
+
+use mpsc::unbounded::{Consumer};
+use {Error};
+
+/// Prepends the length of `payload` to `payload`.
+///
+/// ### Panic
+///
+/// Panics if `payload` is longer than `u32::MAX` bytes.
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let len = payload.len();
+    assert!(len as u64 <= 0xFFFF_FFFF, "frame too large");
+    let mut frame = Vec::with_capacity(4 + len);
+    frame.push((len >> 24) as u8);
+    frame.push((len >> 16) as u8);
+    frame.push((len >> 8) as u8);
+    frame.push(len as u8);
+    frame.extend(payload.iter().cloned());
+    frame
+}
+
+/// Reads length-prefixed frames from the consuming end of an unbounded MPSC channel of
+/// byte chunks.
+pub struct FrameReader<'a> {
+    data: Consumer<'a, Vec<u8>>,
+    // Bytes that have been received but not returned as part of a frame yet.
+    buf: RefCell<Vec<u8>>,
+}
+
+impl<'a> FrameReader<'a> {
+    /// Creates a new frame reader that receives chunks from `data`.
+    pub fn new(data: Consumer<'a, Vec<u8>>) -> FrameReader<'a> {
+        FrameReader { data: data, buf: RefCell::new(vec!()) }
+    }
+
+    /// Returns the payload of the next frame. Blocks until the whole frame has been
+    /// received.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All senders have disconnected before the frame was complete.
+    ///   The bytes of an incomplete frame are kept and can be retrieved with `buffered`.
+    pub fn next_frame(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = self.buf.borrow_mut();
+        loop {
+            if let Some(len) = frame_len(&buf) {
+                let frame = buf[4..4 + len].to_vec();
+                buf.drain(..4 + len);
+                return Ok(frame);
+            }
+            let chunk = try!(self.data.recv_sync());
+            buf.extend(chunk.into_iter());
+        }
+    }
+
+    /// Returns the bytes that have been received but are not part of a complete frame
+    /// yet.
+    pub fn buffered(&self) -> Vec<u8> {
+        self.buf.borrow().clone()
+    }
+
+    /// Returns the underlying consumer. Buffered bytes are lost.
+    pub fn into_inner(self) -> Consumer<'a, Vec<u8>> {
+        self.data
+    }
+}
+
+/// Returns the length of the payload of the first frame in `buf` if the frame is
+/// complete.
+fn frame_len(buf: &[u8]) -> Option<usize> {
+    if buf.len() < 4 {
+        return None;
+    }
+    let len = (buf[0] as usize) << 24 | (buf[1] as usize) << 16 |
+              (buf[2] as usize) << 8 | buf[3] as usize;
+    if buf.len() - 4 < len {
+        None
+    } else {
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mpsc::unbounded::{new};
+    use {Error};
+
+    use super::{FrameReader, encode};
+
+    #[test]
+    fn split_frames() {
+        let (send, recv) = new();
+        let reader = FrameReader::new(recv);
+
+        let mut bytes = encode(b"hello");
+        bytes.extend(encode(b"").into_iter());
+        bytes.extend(encode(&[7; 300]).into_iter());
+        // Two frames in the first chunk, the third split across several chunks.
+        let (a, b) = bytes.split_at(13);
+        send.send(a.to_vec()).unwrap();
+        for chunk in b.chunks(100) {
+            send.send(chunk.to_vec()).unwrap();
+        }
+        send.send(vec!(0, 0)).unwrap();
+        drop(send);
+
+        assert_eq!(reader.next_frame().unwrap(), b"hello".to_vec());
+        assert_eq!(reader.next_frame().unwrap(), vec!());
+        assert_eq!(reader.next_frame().unwrap(), vec!(7; 300));
+        assert_eq!(reader.next_frame().unwrap_err(), Error::Disconnected);
+        assert_eq!(reader.buffered(), vec!(0, 0));
+    }
+}
@@ -93,16 +93,19 @@ extern crate alloc;
 #[cfg(test)] extern crate test;
 
 pub use marker::{Sendable};
+pub use cancel::{CancelToken};
 
-mod sortedvec;
 mod marker;
+mod cancel;
 
 pub mod arc;
+pub mod sortedvec;
 pub mod select;
 pub mod spsc;
 pub mod spmc;
 pub mod mpsc;
 pub mod mpmc;
+pub mod framing;
 
 /// Errors that can happen during receiving and sending.
 ///
@@ -114,4 +117,26 @@ pub enum Error {
     Full,
     Empty,
     Deadlock,
+    /// A `CancelToken` has been cancelled.
+    ///
+    /// Only the cancellation-aware methods, i.e., those that take a `CancelToken`,
+    /// return this error. All other methods can treat it as impossible.
+    Cancelled,
+}
+
+/// The error returned by constructors if the requested capacity can't be supported on
+/// this platform.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CapacityError;
+
+/// The result of a non-blocking receive that distinguishes the two reasons why no
+/// message could be received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TryRecvResult<T> {
+    /// A message has been received.
+    Ok(T),
+    /// The channel is empty.
+    Empty,
+    /// The channel is empty and the sending side has disconnected.
+    Disconnected,
 }
@@ -2,6 +2,9 @@
 ///
 /// If possible you should implement `std::marker::Send` instead of this trait. This trait
 /// exists because `Send` cannot be implemented for `*mut T` and `*const T`.
+///
+/// Every `Send` type is `Sendable`. This includes boxed trait objects such as
+/// `Box<Fn() + Send>`, so they can be sent over all channels without further work.
 pub unsafe trait Sendable { }
 
 unsafe impl<T: Send+?Sized> Sendable for T { }
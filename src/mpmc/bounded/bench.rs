@@ -0,0 +1,42 @@
+use std::{thread};
+
+use test::{Bencher, black_box};
+
+fn contended(b: &mut Bencher, cap: usize, threads: usize) {
+    b.iter(|| {
+        let chan = super::Channel::new(cap);
+        let mut guards = vec!();
+        for _ in 0..threads {
+            let send = chan.clone();
+            guards.push(thread::spawn(move || {
+                for i in 0..128 {
+                    send.send_sync(i).unwrap();
+                }
+            }));
+            let recv = chan.clone();
+            guards.push(thread::spawn(move || {
+                for _ in 0..128 {
+                    black_box(recv.recv_sync().unwrap());
+                }
+            }));
+        }
+        for guard in guards {
+            guard.join().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn contended_tiny_4(b: &mut Bencher) {
+    contended(b, 2, 4);
+}
+
+#[bench]
+fn contended_tiny_16(b: &mut Bencher) {
+    contended(b, 2, 16);
+}
+
+#[bench]
+fn contended_tiny_64(b: &mut Bencher) {
+    contended(b, 2, 64);
+}
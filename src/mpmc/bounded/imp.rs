@@ -3,15 +3,21 @@
 //! 1024cores does not handle ABA!
 
 use std::{ptr, mem};
-use std::sync::atomic::{AtomicUsize, AtomicBool};
+use std::sync::atomic::{self, AtomicUsize, AtomicBool};
 use std::sync::atomic::Ordering::{SeqCst};
 use std::sync::{Mutex, Condvar};
 use alloc::heap::{allocate, deallocate};
 use std::cell::{Cell};
+use std::thread;
+use std::time::{Duration};
 
 use select::{_Selectable, WaitQueue, Payload};
 use alloc::{oom};
-use {Error, Sendable};
+use {Error, Sendable, CapacityError};
+
+// After the `n`-th failed iteration, the CAS loops below spin for `2^n` iterations. Once
+// `n` exceeds this limit, they yield to other threads instead of spinning.
+const SPIN_LIMIT: u32 = 6;
 
 #[cfg(target_pointer_width = "64")]
 type HalfPointer = u32;
@@ -69,21 +75,46 @@ pub struct Packet<'a, T: Sendable+'a> {
     sleep_mutex: Mutex<()>,
     // Number of peers that are awake.
     peers_awake: AtomicUsize,
+    // Number of times we retry before we report a deadlock.
+    deadlock_grace: usize,
 
     // Is any one selecting on this channel?
     wait_queue_used: AtomicBool,
     wait_queue: Mutex<WaitQueue<'a>>,
 }
 
+// Used in the CAS loops below so that contending threads don't livelock a core.
+struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    fn new() -> Backoff {
+        Backoff { step: 0 }
+    }
+
+    fn snooze(&mut self) {
+        if self.step <= SPIN_LIMIT {
+            for _ in 0..1 << self.step {
+                atomic::spin_loop_hint();
+            }
+            self.step += 1;
+        } else {
+            thread::yield_now();
+        }
+    }
+}
+
 impl<'a, T: Sendable+'a> Packet<'a, T> {
-    pub fn new(buf_size: usize) -> Packet<'a, T> {
+    pub fn new(buf_size: usize, deadlock_grace: usize,
+               selects: usize) -> Result<Packet<'a, T>, CapacityError> {
         if buf_size > 1 << (HALF_POINTER_BITS - 1) {
-            panic!("capacity overflow");
+            return Err(CapacityError);
         }
         let cap = buf_size.next_power_of_two();
         let size = cap.checked_mul(mem::size_of::<T>()).unwrap_or(!0);
         if size > !0 >> 1 {
-            panic!("capacity overflow");
+            return Err(CapacityError);
         }
         let buf = if mem::size_of::<T>() == 0 {
             1 as *mut u8
@@ -93,7 +124,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         if buf.is_null() {
             oom();
         }
-        Packet {
+        Ok(Packet {
             id: Cell::new(0),
 
             buf: buf as *mut T,
@@ -110,10 +141,11 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
 
             sleep_mutex: Mutex::new(()),
             peers_awake: AtomicUsize::new(1),
+            deadlock_grace: deadlock_grace,
 
             wait_queue_used: AtomicBool::new(false),
-            wait_queue: Mutex::new(WaitQueue::new()),
-        }
+            wait_queue: Mutex::new(WaitQueue::with_capacity(selects)),
+        })
     }
 
     /// Call this function before any other.
@@ -140,7 +172,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
-    fn notify_wait_queue(&self) {
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
         if self.wait_queue_used.load(SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -152,6 +185,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
     /// Get a position to write to if the queue isn't full
     fn get_write_pos(&self) -> Option<HalfPointer> {
         // See the get_read_pos docs for details.
+        let mut backoff = Backoff::new();
         loop {
             let rsnw = self.read_start_next_write.load(SeqCst);
             let (read_start, next_write) = decompose_pointer(rsnw);
@@ -163,23 +197,27 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
                                                            SeqCst) == rsnw {
                 return Some(next_write);
             }
+            backoff.snooze();
         }
     }
 
     /// `pos` is the position we've written to
     fn set_write_end(&self, pos: HalfPointer) {
         // See the get_read_pos docs for details.
+        //
+        // We have to wait until all writers that got a smaller position have finished.
+        let mut backoff = Backoff::new();
         loop {
             let wenr = self.write_end_next_read.load(SeqCst);
             let (write_end, next_read) = decompose_pointer(wenr);
-            if write_end != pos {
-                continue;
-            }
-            let wenr_new = compose_pointer(pos + 1, next_read);
-            if self.write_end_next_read.compare_and_swap(wenr, wenr_new,
-                                                         SeqCst) == wenr {
-                return;
+            if write_end == pos {
+                let wenr_new = compose_pointer(pos + 1, next_read);
+                if self.write_end_next_read.compare_and_swap(wenr, wenr_new,
+                                                             SeqCst) == wenr {
+                    return;
+                }
             }
+            backoff.snooze();
         }
     }
 
@@ -218,6 +256,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         };
 
         let mut rv = Ok(());
+        let mut grace = self.deadlock_grace;
         let mut guard = self.sleep_mutex.lock().unwrap();
         self.sleeping_senders.fetch_add(1, SeqCst);
         loop {
@@ -232,6 +271,15 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             if self.peers_awake.fetch_sub(1, SeqCst) == 1 &&
                     self.sleeping_receivers.load(SeqCst) == 0 {
                 self.peers_awake.fetch_add(1, SeqCst);
+                if grace > 0 {
+                    // The peers might only be between two operations. Release the lock
+                    // so that they can make progress and try again.
+                    grace -= 1;
+                    drop(guard);
+                    thread::yield_now();
+                    guard = self.sleep_mutex.lock().unwrap();
+                    continue;
+                }
                 rv = Err((val, Error::Deadlock));
                 break;
             } else {
@@ -244,6 +292,30 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         rv
     }
 
+    pub fn send_timeout(&self, val: T, dur: Duration) -> Result<(), (T, Error)> {
+        let mut val = match self.send_async(val, false) {
+            Err(v) => Some(v.0),
+            _ => return Ok(()),
+        };
+
+        // Unlike in send_sync we don't touch peers_awake. We always wake up again, so for
+        // the purpose of deadlock detection we count as awake.
+        let guard = self.sleep_mutex.lock().unwrap();
+        self.sleeping_senders.fetch_add(1, SeqCst);
+        let _guard = self.send_condvar.wait_timeout_with(guard, dur, |_| {
+            match self.send_async(val.take().unwrap(), true) {
+                Err(v) => { val = Some(v.0); false },
+                _ => true,
+            }
+        }).unwrap().0;
+        self.sleeping_senders.fetch_sub(1, SeqCst);
+
+        match val {
+            Some(val) => Err((val, Error::Full)),
+            None => Ok(()),
+        }
+    }
+
     /// Get a position to read from if the queue isn't empty
     fn get_read_pos(&self) -> Option<HalfPointer> {
         // The write_end_next_read field contains two variables: write_end and next_read.
@@ -274,6 +346,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         // It's highly unlikely for this ABA to happen, and on 64bit one might even
         // consider it impossible. After a more careful analysis, a future implementation
         // might change the implementation.
+        let mut backoff = Backoff::new();
         loop {
             let wenr = self.write_end_next_read.load(SeqCst);
             let (write_end, next_read) = decompose_pointer(wenr);
@@ -285,22 +358,25 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
                                                          SeqCst) == wenr {
                 return Some(next_read);
             }
+            backoff.snooze();
         }
     }
 
     /// `pos` is the position we've read from
     fn set_read_start(&self, pos: HalfPointer) {
+        // We have to wait until all readers that got a smaller position have finished.
+        let mut backoff = Backoff::new();
         loop {
             let rsnw = self.read_start_next_write.load(SeqCst);
             let (read_start, next_write) = decompose_pointer(rsnw);
-            if read_start != pos {
-                continue;
-            }
-            let rsnw_new = compose_pointer(pos + 1, next_write);
-            if self.read_start_next_write.compare_and_swap(rsnw, rsnw_new,
-                                                           SeqCst) == rsnw {
-                return;
+            if read_start == pos {
+                let rsnw_new = compose_pointer(pos + 1, next_write);
+                if self.read_start_next_write.compare_and_swap(rsnw, rsnw_new,
+                                                               SeqCst) == rsnw {
+                    return;
+                }
             }
+            backoff.snooze();
         }
     }
 
@@ -336,6 +412,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             return rv;
         }
 
+        let mut grace = self.deadlock_grace;
         let mut guard = self.sleep_mutex.lock().unwrap();
         self.sleeping_receivers.fetch_add(1, SeqCst);
         loop {
@@ -347,6 +424,13 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             if self.peers_awake.fetch_sub(1, SeqCst) == 1 &&
                     self.sleeping_senders.load(SeqCst) == 0 {
                 self.peers_awake.fetch_add(1, SeqCst);
+                if grace > 0 {
+                    grace -= 1;
+                    drop(guard);
+                    thread::yield_now();
+                    guard = self.sleep_mutex.lock().unwrap();
+                    continue;
+                }
                 rv = Err(Error::Deadlock);
                 break;
             } else {
@@ -358,6 +442,41 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
 
         rv
     }
+
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        let mut rv = self.recv_async(false);
+        if rv.is_ok() {
+            return rv;
+        }
+
+        // See the docs in send_timeout.
+        let guard = self.sleep_mutex.lock().unwrap();
+        self.sleeping_receivers.fetch_add(1, SeqCst);
+        let _guard = self.recv_condvar.wait_timeout_with(guard, dur, |_| {
+            rv = self.recv_async(true);
+            rv.is_ok()
+        }).unwrap().0;
+        self.sleeping_receivers.fetch_sub(1, SeqCst);
+
+        rv
+    }
+
+    pub fn has_sleeping_senders(&self) -> bool {
+        self.sleeping_senders.load(SeqCst) > 0
+    }
+
+    pub fn has_sleeping_receivers(&self) -> bool {
+        self.sleeping_receivers.load(SeqCst) > 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap_mask as usize + 1
+    }
+
+    #[cfg(test)]
+    pub fn select_capacity(&self) -> usize {
+        self.wait_queue.lock().unwrap().capacity()
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
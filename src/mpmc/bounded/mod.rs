@@ -7,12 +7,16 @@
 //! This implementation suffers from some performance problems when the number of active
 //! endpoints is larger than the number of cpu cores.
 
+use std::time::{Duration};
+use std::{fmt};
+
 use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
-use {Error, Sendable};
+use select::{Selectable, RecvSelectable, _Selectable};
+use {Error, Sendable, CapacityError};
 
 mod imp;
 #[cfg(test)] mod test;
+#[cfg(test)] mod bench;
 
 /// An endpoint of a bounded MPMC channel.
 pub struct Channel<'a, T: Sendable+'a> {
@@ -30,9 +34,57 @@ impl<'a, T: Sendable+'a> Channel<'a, T> {
     /// - `sizeof(usize) == 8 && cap > 2^31`,
     /// - `next_power_of_two(cap) * sizeof(T) >= isize::MAX`.
     pub fn new(cap: usize) -> Channel<'a, T> {
-        let packet = Arc::new(imp::Packet::new(cap));
+        match Channel::try_new(cap) {
+            Ok(c) => c,
+            Err(_) => panic!("capacity overflow"),
+        }
+    }
+
+    /// Creates a new bounded MPMC channel with capacity at least `cap`.
+    ///
+    /// ### Error
+    ///
+    /// Returns an error under the conditions under which `new` panics.
+    pub fn try_new(cap: usize) -> Result<Channel<'a, T>, CapacityError> {
+        Channel::try_new_with_select_capacity(cap, 0, 0)
+    }
+
+    /// Creates a new bounded MPMC channel with capacity at least `cap`.
+    ///
+    /// Before a blocking operation reports a `Deadlock`, it retries the operation up to
+    /// `deadlock_grace` times and yields to other threads in between. This reduces the
+    /// number of spurious deadlocks reported when all peers are only briefly blocked at
+    /// the same time.
+    ///
+    /// ### Panic
+    ///
+    /// See `new`.
+    pub fn new_with_options(cap: usize, deadlock_grace: usize) -> Channel<'a, T> {
+        Channel::new_with_select_capacity(cap, deadlock_grace, 0)
+    }
+
+    /// Like `new_with_options` but reserves space for `selects` `Select` objects that
+    /// wait on this channel at the same time.
+    ///
+    /// This avoids reallocations while `Select` objects are added if many endpoints of
+    /// the channel are selected on.
+    ///
+    /// ### Panic
+    ///
+    /// See `new`.
+    pub fn new_with_select_capacity(cap: usize, deadlock_grace: usize,
+                                    selects: usize) -> Channel<'a, T> {
+        match Channel::try_new_with_select_capacity(cap, deadlock_grace, selects) {
+            Ok(c) => c,
+            Err(_) => panic!("capacity overflow"),
+        }
+    }
+
+    fn try_new_with_select_capacity(cap: usize, deadlock_grace: usize, selects: usize)
+                                    -> Result<Channel<'a, T>, CapacityError> {
+        let packet = Arc::new(try!(imp::Packet::new(cap, deadlock_grace, selects)));
         packet.set_id(packet.unique_id());
-        Channel { data: packet }
+        Ok(Channel { data: packet })
     }
 
     /// Sends a message over the channel. Blocks if the channel is full.
@@ -71,6 +123,63 @@ impl<'a, T: Sendable+'a> Channel<'a, T> {
     pub fn recv_async(&self) -> Result<T, Error> {
         self.data.recv_async(false)
     }
+
+    /// Sends a message over the channel. Blocks for at most `dur` if the channel is full.
+    ///
+    /// Unlike `send_sync`, this function never reports a deadlock.
+    ///
+    /// ### Error
+    ///
+    /// - `Full` - The buffer was still full when the timeout expired.
+    pub fn send_timeout(&self, val: T, dur: Duration) -> Result<(), (T, Error)> {
+        self.data.send_timeout(val, dur)
+    }
+
+    /// Receives a message from the channel. Blocks for at most `dur` if the channel is
+    /// empty.
+    ///
+    /// Unlike `recv_sync`, this function never reports a deadlock.
+    ///
+    /// ### Error
+    ///
+    /// - `Empty` - The buffer was still empty when the timeout expired.
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        self.data.recv_timeout(dur)
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns whether another endpoint is currently blocked in `recv_sync`.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn has_blocked_receiver(&self) -> bool {
+        self.data.has_sleeping_receivers()
+    }
+
+    /// Returns whether another endpoint is currently blocked in `send_sync`.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn has_blocked_sender(&self) -> bool {
+        self.data.has_sleeping_senders()
+    }
+
+    /// Splits the channel into an endpoint that can only send and an endpoint that can
+    /// only receive.
+    ///
+    /// Both endpoints count as peers for the purpose of deadlock detection.
+    pub fn into_sender_receiver(self) -> (Sender<'a, T>, Receiver<'a, T>) {
+        (Sender { data: self.clone() }, Receiver { data: self })
+    }
+
+    /// Returns the number of `Select` objects the channel can hold without reallocating.
+    #[cfg(test)]
+    fn select_capacity(&self) -> usize {
+        self.data.select_capacity()
+    }
 }
 
 unsafe impl<'a, T: Sendable> Sync for Channel<'a, T> { }
@@ -98,3 +207,139 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Channel<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Channel<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Channel::recv_async(self)
+    }
+}
+
+/// An endpoint of a bounded MPMC channel that can only send messages.
+///
+/// See `Channel::into_sender_receiver`.
+pub struct Sender<'a, T: Sendable+'a> {
+    data: Channel<'a, T>,
+}
+
+impl<'a, T: Sendable+'a> Sender<'a, T> {
+    /// Sends a message over the channel. Blocks if the channel is full.
+    ///
+    /// ### Error
+    ///
+    /// - `Deadlock` - All other endpoints are currently blocked trying to send a message.
+    pub fn send_sync(&self, val: T) -> Result<(), (T, Error)> {
+        self.data.send_sync(val)
+    }
+
+    /// Sends a message over the channel. Does not block if the channel is full.
+    ///
+    /// ### Error
+    ///
+    /// - `Full` - The buffer is full.
+    pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
+        self.data.send_async(val)
+    }
+
+    /// Returns whether another endpoint is currently blocked in `recv_sync`.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn has_blocked_receiver(&self) -> bool {
+        self.data.has_blocked_receiver()
+    }
+}
+
+impl<'a, T: Sendable+'a> Clone for Sender<'a, T> {
+    fn clone(&self) -> Sender<'a, T> {
+        Sender { data: self.data.clone() }
+    }
+}
+
+/// An endpoint of a bounded MPMC channel that can only receive messages.
+///
+/// See `Channel::into_sender_receiver`.
+pub struct Receiver<'a, T: Sendable+'a> {
+    data: Channel<'a, T>,
+}
+
+impl<'a, T: Sendable+'a> Receiver<'a, T> {
+    /// Receives a message from the channel. Blocks if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Deadlock` - All other endpoints are currently blocked trying to receive a
+    ///   message.
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        self.data.recv_sync()
+    }
+
+    /// Receives a message over the channel. Does not block if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Empty` - The buffer is empty.
+    pub fn recv_async(&self) -> Result<T, Error> {
+        self.data.recv_async()
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.poke_selects()
+    }
+
+    /// Returns whether another endpoint is currently blocked in `send_sync`.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn has_blocked_sender(&self) -> bool {
+        self.data.has_blocked_sender()
+    }
+}
+
+impl<'a, T: Sendable+'a> Clone for Receiver<'a, T> {
+    fn clone(&self) -> Receiver<'a, T> {
+        Receiver { data: self.data.clone() }
+    }
+}
+
+impl<'a, T: Sendable+'a> Selectable<'a> for Receiver<'a, T> {
+    fn id(&self) -> usize {
+        self.data.id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        self.data.as_selectable()
+    }
+}
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Receiver<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Receiver::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Channel<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpmc::bounded::Channel")
+         .field("id", &self.data.unique_id())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Sender<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpmc::bounded::Sender")
+         .field("id", &self.data.data.unique_id())
+         .field("capacity", &self.data.data.capacity())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Receiver<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpmc::bounded::Receiver")
+         .field("id", &self.data.data.unique_id())
+         .field("capacity", &self.data.data.capacity())
+         .finish()
+    }
+}
@@ -2,9 +2,10 @@ use std::sync::{Arc};
 use std::thread::{self, sleep_ms};
 use std::sync::atomic::{AtomicUsize};
 use std::sync::atomic::Ordering::{SeqCst};
+use std::time::{Duration};
 
 use select::{Select, Selectable};
-use {Error};
+use {Error, CapacityError};
 
 fn ms_sleep(ms: i64) {
     sleep_ms(ms as u32);
@@ -36,6 +37,43 @@ fn send_sync() {
     assert_eq!(channel.send_sync(1).unwrap_err().1, Error::Deadlock);
 }
 
+#[test]
+fn recv_sync_deadlock_grace() {
+    let channel = super::Channel::<u8>::new_with_options(2, 10);
+    assert_eq!(channel.recv_sync().unwrap_err(), Error::Deadlock);
+}
+
+#[test]
+fn send_sync_deadlock_grace() {
+    let channel = super::Channel::<u8>::new_with_options(1, 10);
+    channel.send_sync(1).unwrap();
+    assert_eq!(channel.send_sync(1).unwrap_err().1, Error::Deadlock);
+}
+
+fn recv_sync_peer_arrives(grace: usize) -> Result<u8, Error> {
+    let chan = super::Channel::new_with_options(2, grace);
+    let _t = {
+        let chan = &chan;
+        thread::scoped(move || {
+            ms_sleep(10);
+            let chan2 = chan.clone();
+            chan2.send_sync(1).unwrap();
+        })
+    };
+    chan.recv_sync()
+}
+
+#[test]
+fn recv_sync_peer_arrives_no_grace() {
+    assert_eq!(recv_sync_peer_arrives(0).unwrap_err(), Error::Deadlock);
+}
+
+#[test]
+fn recv_sync_peer_arrives_within_grace() {
+    // The grace period is long enough to cover the 10ms until the new peer shows up.
+    assert_eq!(recv_sync_peer_arrives(10_000_000).unwrap(), 1);
+}
+
 #[test]
 fn send_send() {
     let channel = super::Channel::new(1);
@@ -97,15 +135,23 @@ fn send_5_recv_5() {
 }
 
 fn multiple_producers_multiple_consumers(buf_size: usize) {
-    const NUM_THREADS_PER_END: usize = 2;
+    multiple_producers_multiple_consumers_grace(buf_size, 0);
+}
+
+fn multiple_producers_multiple_consumers_grace(buf_size: usize, grace: usize) {
+    multiple_producers_multiple_consumers_threads(buf_size, grace, 2);
+}
+
+fn multiple_producers_multiple_consumers_threads(buf_size: usize, grace: usize,
+                                                 num_threads_per_end: usize) {
     const NUM_PER_THREAD: usize = 1000;
-    const RESULT: usize = (NUM_THREADS_PER_END*NUM_PER_THREAD-1)
-                                    *(NUM_THREADS_PER_END*NUM_PER_THREAD)/2;
+    let result = (num_threads_per_end*NUM_PER_THREAD-1)
+                                    *(num_threads_per_end*NUM_PER_THREAD)/2;
 
-    let chan = super::Channel::<usize>::new(buf_size);
+    let chan = super::Channel::<usize>::new_with_options(buf_size, grace);
     let sum = Arc::new(AtomicUsize::new(0));
     let mut threads = vec!();
-    for _ in 0..NUM_THREADS_PER_END {
+    for _ in 0..num_threads_per_end {
         let chan2 = chan.clone();
         let sum2 = sum.clone();
         threads.push(thread::scoped(move || {
@@ -114,7 +160,7 @@ fn multiple_producers_multiple_consumers(buf_size: usize) {
             }
         }));
     }
-    for i in 0..NUM_THREADS_PER_END {
+    for i in 0..num_threads_per_end {
         let chan2 = chan.clone();
         threads.push(thread::scoped(move || {
             for j in (i*NUM_PER_THREAD..(i+1)*NUM_PER_THREAD) {
@@ -124,7 +170,7 @@ fn multiple_producers_multiple_consumers(buf_size: usize) {
     }
     drop(chan);
     drop(threads);
-    assert_eq!(sum.swap(0, SeqCst), RESULT);
+    assert_eq!(sum.swap(0, SeqCst), result);
 }
 
 #[test]
@@ -147,6 +193,17 @@ fn multiple_producers_multiple_consumers_1000() {
     multiple_producers_multiple_consumers(1000);
 }
 
+#[test]
+fn multiple_producers_multiple_consumers_oversubscribed() {
+    // Far more threads than cores so that the CAS loops are contended.
+    multiple_producers_multiple_consumers_threads(4, 0, 32);
+}
+
+#[test]
+fn multiple_producers_multiple_consumers_grace_1() {
+    multiple_producers_multiple_consumers_grace(1, 100);
+}
+
 #[test]
 fn select_no_wait() {
     let chan = super::Channel::new(2);
@@ -180,3 +237,125 @@ fn select_wait() {
 
     assert_eq!(buf[0], chan.id());
 }
+
+#[test]
+fn select_clones() {
+    let chan = super::Channel::new(2);
+    let chan2 = chan.clone();
+    assert_eq!(chan.id(), chan2.id());
+
+    let select = Select::new();
+    select.add(&chan);
+    select.add(&chan2);
+    assert_eq!(select.len(), 1);
+
+    assert!(select.remove(&chan2));
+    assert_eq!(select.len(), 1);
+    chan.send_sync(1u8).unwrap();
+    let mut buf = [0];
+    assert_eq!(select.wait(&mut buf), &mut [chan.id()][..]);
+
+    assert!(select.remove(&chan));
+    assert!(select.is_empty());
+}
+
+#[test]
+fn has_blocked_receiver() {
+    let chan = super::Channel::new(1);
+    let chan2 = chan.clone();
+    assert!(!chan.has_blocked_receiver());
+    thread::spawn(move || {
+        assert_eq!(chan2.recv_sync().unwrap(), 1u8);
+    });
+    ms_sleep(100);
+    assert!(chan.has_blocked_receiver());
+    assert!(!chan.has_blocked_sender());
+    chan.send_sync(1).unwrap();
+}
+
+#[test]
+fn has_blocked_sender() {
+    let chan = super::Channel::new(1);
+    chan.send_sync(1u8).unwrap();
+    let chan2 = chan.clone();
+    assert!(!chan.has_blocked_sender());
+    thread::spawn(move || {
+        chan2.send_sync(2).unwrap();
+    });
+    ms_sleep(100);
+    assert!(chan.has_blocked_sender());
+    assert!(!chan.has_blocked_receiver());
+    assert_eq!(chan.recv_sync().unwrap(), 1);
+}
+
+#[test]
+fn sender_receiver() {
+    let (send, recv) = super::Channel::new(2).into_sender_receiver();
+    let send2 = send.clone();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send2.send_sync(1u8).unwrap();
+    });
+
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    send.send_async(2).unwrap();
+    assert_eq!(recv.clone().recv_async().unwrap(), 2);
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
+}
+
+#[test]
+fn recv_timeout() {
+    let chan = super::Channel::<u8>::new(1);
+    assert_eq!(chan.recv_timeout(Duration::from_millis(10)).unwrap_err(), Error::Empty);
+
+    let chan2 = chan.clone();
+    thread::spawn(move || {
+        ms_sleep(100);
+        chan2.send_sync(1).unwrap();
+    });
+    assert_eq!(chan.recv_timeout(Duration::from_millis(1000)).unwrap(), 1);
+}
+
+#[test]
+fn send_timeout() {
+    let chan = super::Channel::new(1);
+    chan.send_sync(1u8).unwrap();
+    assert_eq!(chan.send_timeout(2, Duration::from_millis(10)).unwrap_err(),
+               (2, Error::Full));
+
+    let chan2 = chan.clone();
+    thread::spawn(move || {
+        ms_sleep(100);
+        assert_eq!(chan2.recv_sync().unwrap(), 1);
+    });
+    chan.send_timeout(2, Duration::from_millis(1000)).unwrap();
+    assert_eq!(chan.recv_async().unwrap(), 2);
+}
+
+#[test]
+fn select_capacity() {
+    let chan = super::Channel::new_with_select_capacity(2, 0, 4);
+    assert!(chan.select_capacity() >= 4);
+    assert_eq!(super::Channel::<u8>::new(2).select_capacity(), 0);
+    let selects: Vec<_> = (0..8).map(|_| Select::new()).collect();
+    for select in &selects {
+        select.add(&chan);
+    }
+    chan.send_sync(1u8).unwrap();
+    for select in &selects {
+        assert_eq!(select.wait(&mut [0]), &mut [chan.id()][..]);
+    }
+}
+
+#[test]
+fn try_new() {
+    assert!(super::Channel::<u8>::try_new(2).is_ok());
+    assert_eq!(super::Channel::<u8>::try_new(!0).unwrap_err(), CapacityError);
+}
+
+#[test]
+#[should_panic]
+fn new_capacity_overflow() {
+    super::Channel::<u8>::new(!0);
+}
@@ -10,3 +10,4 @@
 //! there is only one endpoint per thread.
 
 pub mod bounded;
+pub mod unbounded;
@@ -0,0 +1,207 @@
+use std::sync::atomic::{AtomicPtr, AtomicUsize, AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst};
+use std::sync::{Mutex, Condvar};
+use std::{mem, ptr};
+use std::cell::{Cell};
+
+use select::{_Selectable, WaitQueue, Payload};
+use {Error, Sendable};
+
+pub struct Packet<'a, T: Sendable+'a> {
+    // The id of this channel. The address of the `arc::Inner` that contains this channel.
+    id: Cell<usize>,
+
+    // The next node we can read from. This is null while a receiver is reading from the
+    // node. See `recv_async`.
+    read_end: AtomicPtr<Node<T>>,
+    // The next node we write to.
+    write_end: AtomicPtr<Node<T>>,
+
+    // The number of nodes ready for reading.
+    num_queued: AtomicUsize,
+
+    // Number of receivers that are currently sleeping.
+    sleeping_receivers: AtomicUsize,
+    // Condvar the receivers are sleeping on.
+    recv_condvar: Condvar,
+    // Mutex that protects the atomic variable above and the one below.
+    sleep_mutex: Mutex<()>,
+    // Number of peers that are awake.
+    peers_awake: AtomicUsize,
+
+    // Is someone selecting on this channel?
+    wait_queue_used: AtomicBool,
+    wait_queue: Mutex<WaitQueue<'a>>,
+}
+
+struct Node<T: Sendable> {
+    next: AtomicPtr<Node<T>>,
+    val: Option<T>,
+}
+
+impl<T: Sendable> Node<T> {
+    // Creates and forgets a new node.
+    fn new() -> *mut Node<T> {
+        let mut node: Box<Node<T>> = Box::new(Node {
+            next: AtomicPtr::new(ptr::null_mut()),
+            val: None
+        });
+        let ptr = &mut *node as *mut _;
+        mem::forget(node);
+        ptr
+    }
+}
+
+impl<'a, T: Sendable+'a> Packet<'a, T> {
+    pub fn new() -> Packet<'a, T> {
+        let ptr = Node::new();
+        Packet {
+            id: Cell::new(0),
+
+            read_end: AtomicPtr::new(ptr),
+            write_end: AtomicPtr::new(ptr),
+
+            num_queued: AtomicUsize::new(0),
+
+            sleeping_receivers: AtomicUsize::new(0),
+            recv_condvar: Condvar::new(),
+            sleep_mutex: Mutex::new(()),
+            peers_awake: AtomicUsize::new(1),
+
+            wait_queue_used: AtomicBool::new(false),
+            wait_queue: Mutex::new(WaitQueue::new()),
+        }
+    }
+
+    /// Call this function before any other.
+    pub fn set_id(&self, id: usize) {
+        self.id.set(id);
+        self.wait_queue.lock().unwrap().set_id(id);
+    }
+
+    /// Call this function when the channel is cloned.
+    pub fn add_peer(&self) {
+        self.peers_awake.fetch_add(1, SeqCst);
+    }
+
+    /// Call this function when a peer is dropped.
+    pub fn remove_peer(&self) {
+        if self.peers_awake.fetch_sub(1, SeqCst) == 1 {
+            // Everyone else is sleeping. Wake one of them up so that it can report the
+            // deadlock.
+            let _guard = self.sleep_mutex.lock().unwrap();
+            self.recv_condvar.notify_one();
+            self.notify_wait_queue();
+        }
+    }
+
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
+        if self.wait_queue_used.load(SeqCst) {
+            let mut wait_queue = self.wait_queue.lock().unwrap();
+            if wait_queue.notify() == 0 {
+                self.wait_queue_used.store(false, SeqCst);
+            }
+        }
+    }
+
+    pub fn send(&self, val: T) {
+        // See the comment in the unbounded MPSC implementation.
+        let new_end = Node::new();
+        let write_end = self.write_end.swap(new_end, SeqCst);
+        unsafe {
+            (*write_end).val = Some(val);
+            (*write_end).next.store(new_end, SeqCst);
+        }
+        self.num_queued.fetch_add(1, SeqCst);
+
+        if self.sleeping_receivers.load(SeqCst) > 0 {
+            let _guard = self.sleep_mutex.lock().unwrap();
+            self.recv_condvar.notify_one();
+        }
+
+        self.notify_wait_queue();
+    }
+
+    pub fn recv_async(&self) -> Result<T, Error> {
+        if self.num_queued.load(SeqCst) == 0 {
+            return Err(Error::Empty);
+        }
+
+        // See the comment in the unbounded SPMC implementation.
+        let mut read_end = ptr::null_mut();
+        while read_end.is_null() {
+            read_end = self.read_end.swap(read_end, SeqCst);
+        }
+        let next = unsafe { (*read_end).next.load(SeqCst) };
+        if !next.is_null() {
+            self.read_end.store(next, SeqCst);
+            self.num_queued.fetch_sub(1, SeqCst);
+            let mut node = unsafe { mem::transmute::<_, Box<Node<T>>>(read_end) };
+            Ok(node.val.take().unwrap())
+        } else {
+            // A sender has incremented `num_queued` but the node we're looking at is
+            // still being written by another sender.
+            self.read_end.store(read_end, SeqCst);
+            Err(Error::Empty)
+        }
+    }
+
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        let mut rv = self.recv_async();
+        if rv.is_ok() {
+            return rv;
+        }
+
+        let mut guard = self.sleep_mutex.lock().unwrap();
+        self.sleeping_receivers.fetch_add(1, SeqCst);
+        loop {
+            rv = self.recv_async();
+            if rv.is_ok() {
+                break;
+            }
+            // Sending never blocks. If all peers are sleeping in `recv_sync`, nobody can
+            // ever wake us up.
+            if self.peers_awake.fetch_sub(1, SeqCst) == 1 {
+                self.peers_awake.fetch_add(1, SeqCst);
+                rv = Err(Error::Deadlock);
+                break;
+            }
+            guard = self.recv_condvar.wait(guard).unwrap();
+            self.peers_awake.fetch_add(1, SeqCst);
+        }
+        self.sleeping_receivers.fetch_sub(1, SeqCst);
+
+        rv
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Packet<'a, T> { }
+
+impl<'a, T: Sendable+'a> Drop for Packet<'a, T> {
+    fn drop(&mut self) {
+        while self.recv_async().is_ok() { }
+        unsafe { ptr::read(self.read_end.load(SeqCst)); }
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
+    fn ready(&self) -> bool {
+        self.peers_awake.load(SeqCst) == 0 || self.num_queued.load(SeqCst) > 0
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        let mut wait_queue = self.wait_queue.lock().unwrap();
+        if wait_queue.add(load) > 0 {
+            self.wait_queue_used.store(true, SeqCst);
+        }
+    }
+
+    fn unregister(&self, id: usize) {
+        let mut wait_queue = self.wait_queue.lock().unwrap();
+        if wait_queue.remove(id) == 0 {
+            self.wait_queue_used.store(false, SeqCst);
+        }
+    }
+}
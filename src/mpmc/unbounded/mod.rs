@@ -0,0 +1,93 @@
+//! An unbounded MPMC channel.
+//!
+//! See the documentation of the parent module and the unbounded SPSC docs for details.
+//!
+//! Since sending never blocks, the only deadlock that can be detected is when all
+//! endpoints are trying to receive from an empty channel at the same time.
+
+use std::{fmt};
+
+use arc::{Arc, ArcTrait};
+use select::{Selectable, RecvSelectable, _Selectable};
+use {Error, Sendable};
+
+mod imp;
+#[cfg(test)] mod test;
+
+/// An endpoint of an unbounded MPMC channel.
+pub struct Channel<'a, T: Sendable+'a> {
+    data: Arc<imp::Packet<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> Channel<'a, T> {
+    /// Creates a new unbounded MPMC channel.
+    pub fn new() -> Channel<'a, T> {
+        let packet = Arc::new(imp::Packet::new());
+        packet.set_id(packet.unique_id());
+        Channel { data: packet }
+    }
+
+    /// Appends a message to the channel. Never blocks.
+    pub fn send(&self, val: T) {
+        self.data.send(val)
+    }
+
+    /// Receives a message from the channel. Blocks if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Deadlock` - All other endpoints are currently blocked trying to receive a
+    ///   message.
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        self.data.recv_sync()
+    }
+
+    /// Receives a message from the channel. Does not block if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Empty` - The channel is empty.
+    pub fn recv_async(&self) -> Result<T, Error> {
+        self.data.recv_async()
+    }
+}
+
+unsafe impl<'a, T: Sendable> Sync for Channel<'a, T> { }
+unsafe impl<'a, T: Sendable> Send for Channel<'a, T> { }
+
+impl<'a, T: Sendable+'a> Clone for Channel<'a, T> {
+    fn clone(&self) -> Channel<'a, T> {
+        self.data.add_peer();
+        Channel { data: self.data.clone(), }
+    }
+}
+
+impl<'a, T: Sendable+'a> Drop for Channel<'a, T> {
+    fn drop(&mut self) {
+        self.data.remove_peer();
+    }
+}
+
+impl<'a, T: Sendable+'a> Selectable<'a> for Channel<'a, T> {
+    fn id(&self) -> usize {
+        self.data.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
+    }
+}
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Channel<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Channel::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Channel<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpmc::unbounded::Channel")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
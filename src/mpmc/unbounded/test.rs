@@ -0,0 +1,164 @@
+use std::sync::{Arc};
+use std::thread::{self, sleep_ms};
+use std::sync::atomic::{AtomicUsize};
+use std::sync::atomic::Ordering::{SeqCst};
+
+use select::{Select, Selectable};
+use {Error};
+
+fn ms_sleep(ms: i64) {
+    sleep_ms(ms as u32);
+}
+
+#[test]
+fn send_recv() {
+    let channel = super::Channel::new();
+    channel.send(1u8);
+    assert_eq!(channel.recv_async().unwrap(), 1u8);
+}
+
+#[test]
+fn recv_async() {
+    let channel = super::Channel::<u8>::new();
+    assert_eq!(channel.recv_async().unwrap_err(), Error::Empty);
+}
+
+#[test]
+fn recv_sync() {
+    let channel = super::Channel::<u8>::new();
+    assert_eq!(channel.recv_sync().unwrap_err(), Error::Deadlock);
+}
+
+#[test]
+fn recv_sync_deadlock_drop() {
+    let chan = super::Channel::<u8>::new();
+    let chan2 = chan.clone();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(chan2);
+    });
+
+    assert_eq!(chan.recv_sync().unwrap_err(), Error::Deadlock);
+}
+
+#[test]
+fn sleep_send_recv() {
+    let chan = super::Channel::new();
+    let chan2 = chan.clone();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        chan2.send(1u8);
+    });
+
+    assert_eq!(chan.recv_sync().unwrap(), 1);
+}
+
+#[test]
+fn send_sleep_recv() {
+    let chan = super::Channel::new();
+    let chan2 = chan.clone();
+
+    thread::spawn(move || {
+        chan2.send(1u8);
+    });
+
+    ms_sleep(100);
+    assert_eq!(chan.recv_sync().unwrap(), 1);
+}
+
+#[test]
+fn send_sleep_recv_async() {
+    let chan = super::Channel::new();
+    let chan2 = chan.clone();
+
+    thread::spawn(move || {
+        chan2.send(1u8);
+    });
+
+    ms_sleep(100);
+    assert_eq!(chan.recv_async().unwrap(), 1);
+}
+
+#[test]
+fn send_5_recv_5() {
+    let chan = super::Channel::new();
+    chan.send(1u8);
+    chan.send(2u8);
+    chan.send(3u8);
+    chan.send(4u8);
+    chan.send(5u8);
+    assert_eq!(chan.recv_sync().unwrap(), 1);
+    assert_eq!(chan.recv_sync().unwrap(), 2);
+    assert_eq!(chan.recv_sync().unwrap(), 3);
+    assert_eq!(chan.recv_sync().unwrap(), 4);
+    assert_eq!(chan.recv_sync().unwrap(), 5);
+    assert_eq!(chan.recv_async().unwrap_err(), Error::Empty);
+}
+
+#[test]
+fn multiple_producers_multiple_consumers() {
+    const NUM_THREADS_PER_END: usize = 2;
+    const NUM_PER_THREAD: usize = 1000;
+    const RESULT: usize = (NUM_THREADS_PER_END*NUM_PER_THREAD-1)
+                                    *(NUM_THREADS_PER_END*NUM_PER_THREAD)/2;
+
+    let chan = super::Channel::<usize>::new();
+    let sum = Arc::new(AtomicUsize::new(0));
+    let mut threads = vec!();
+    for _ in 0..NUM_THREADS_PER_END {
+        let chan2 = chan.clone();
+        let sum2 = sum.clone();
+        threads.push(thread::scoped(move || {
+            while let Ok(n) = chan2.recv_sync() {
+                sum2.fetch_add(n, SeqCst);
+            }
+        }));
+    }
+    for i in 0..NUM_THREADS_PER_END {
+        let chan2 = chan.clone();
+        threads.push(thread::scoped(move || {
+            for j in (i*NUM_PER_THREAD..(i+1)*NUM_PER_THREAD) {
+                chan2.send(j);
+            }
+        }));
+    }
+    drop(chan);
+    drop(threads);
+    assert_eq!(sum.swap(0, SeqCst), RESULT);
+}
+
+#[test]
+fn select_no_wait() {
+    let chan = super::Channel::new();
+
+    chan.send(1u8);
+
+    let select = Select::new();
+    select.add(&chan);
+
+    let mut buf = [0];
+    select.wait(&mut buf);
+
+    assert_eq!(buf[0], chan.id());
+}
+
+#[test]
+fn select_wait() {
+    let chan = super::Channel::new();
+    let chan2 = chan.clone();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        chan2.send(1u8);
+    });
+
+    let select = Select::new();
+    select.add(&chan);
+
+    let mut buf = [0];
+    select.wait(&mut buf);
+
+    assert_eq!(buf[0], chan.id());
+}
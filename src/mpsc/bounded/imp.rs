@@ -0,0 +1,270 @@
+use std::collections::{VecDeque};
+use std::sync::atomic::{AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst};
+use std::sync::{Mutex, Condvar};
+use std::{cmp};
+
+use arc::{Arc};
+use select::{_Selectable, WaitQueue, Payload};
+use {Error, Sendable};
+
+pub struct Packet<'a, T: Sendable+'a> {
+    // The maximum number of messages in the buffer.
+    cap: usize,
+
+    inner: Mutex<Inner<T>>,
+
+    // Condvar the receiver is waiting on.
+    recv_condvar: Condvar,
+    // Condvar the senders are waiting on.
+    send_condvar: Condvar,
+
+    // Is any one selecting on this channel? The wait queues are not part of `inner`
+    // because `ready` acquires the `inner` lock and therefore we must never hold it while
+    // we interact with a wait queue.
+    wait_queue_used: AtomicBool,
+    wait_queue: Mutex<WaitQueue<'a>>,
+
+    // Is any one selecting on the send side of this channel?
+    send_wait_queue_used: AtomicBool,
+    send_wait_queue: Mutex<WaitQueue<'a>>,
+}
+
+struct Inner<T> {
+    buf: VecDeque<T>,
+
+    num_senders: usize,
+    receiver_disconnected: bool,
+}
+
+impl<'a, T: Sendable+'a> Packet<'a, T> {
+    pub fn new(cap: usize) -> Packet<'a, T> {
+        let cap = cmp::max(cap, 1);
+        Packet {
+            cap: cap,
+
+            inner: Mutex::new(Inner {
+                buf: VecDeque::with_capacity(cap),
+                num_senders: 1,
+                receiver_disconnected: false,
+            }),
+
+            recv_condvar: Condvar::new(),
+            send_condvar: Condvar::new(),
+
+            wait_queue_used: AtomicBool::new(false),
+            wait_queue: Mutex::new(WaitQueue::new()),
+
+            send_wait_queue_used: AtomicBool::new(false),
+            send_wait_queue: Mutex::new(WaitQueue::new()),
+        }
+    }
+
+    /// Call this function before any other.
+    pub fn set_id(&self, id: usize) {
+        self.wait_queue.lock().unwrap().set_id(id);
+    }
+
+    /// Call this function before the send side is used with `Select`.
+    pub fn set_send_id(&self, id: usize) {
+        self.send_wait_queue.lock().unwrap().set_id(id);
+    }
+
+    /// Call this function when the sender is cloned.
+    pub fn add_sender(&self) {
+        self.inner.lock().unwrap().num_senders += 1;
+    }
+
+    /// Call this function when a sender is dropped.
+    pub fn remove_sender(&self) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.num_senders -= 1;
+            if inner.num_senders > 0 {
+                return;
+            }
+            self.recv_condvar.notify_one();
+        }
+        self.notify_wait_queue();
+    }
+
+    /// Call this function when the consumer is dropped.
+    pub fn remove_receiver(&self) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.receiver_disconnected = true;
+            self.send_condvar.notify_all();
+        }
+        self.notify_send_wait_queue();
+    }
+
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
+        if self.wait_queue_used.load(SeqCst) {
+            let mut wait_queue = self.wait_queue.lock().unwrap();
+            if wait_queue.notify() == 0 {
+                self.wait_queue_used.store(false, SeqCst);
+            }
+        }
+    }
+
+    /// Notifies everyone selecting on the send side of this channel.
+    pub fn notify_send_wait_queue(&self) {
+        if self.send_wait_queue_used.load(SeqCst) {
+            let mut wait_queue = self.send_wait_queue.lock().unwrap();
+            if wait_queue.notify() == 0 {
+                self.send_wait_queue_used.store(false, SeqCst);
+            }
+        }
+    }
+
+    pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.receiver_disconnected {
+                return Err((val, Error::Disconnected));
+            }
+            if inner.buf.len() == self.cap {
+                return Err((val, Error::Full));
+            }
+            inner.buf.push_back(val);
+            self.recv_condvar.notify_one();
+        }
+        self.notify_wait_queue();
+        Ok(())
+    }
+
+    pub fn send_sync(&self, val: T) -> Result<(), (T, Error)> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            loop {
+                if inner.receiver_disconnected {
+                    return Err((val, Error::Disconnected));
+                }
+                if inner.buf.len() < self.cap {
+                    break;
+                }
+                inner = self.send_condvar.wait(inner).unwrap();
+            }
+            inner.buf.push_back(val);
+            self.recv_condvar.notify_one();
+        }
+        self.notify_wait_queue();
+        Ok(())
+    }
+
+    pub fn recv_async(&self) -> Result<T, Error> {
+        let val = {
+            let mut inner = self.inner.lock().unwrap();
+            match inner.buf.pop_front() {
+                Some(val) => val,
+                None => {
+                    return if inner.num_senders == 0 {
+                        Err(Error::Disconnected)
+                    } else {
+                        Err(Error::Empty)
+                    };
+                },
+            }
+        };
+        self.send_condvar.notify_one();
+        self.notify_send_wait_queue();
+        Ok(val)
+    }
+
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        let val;
+        {
+            let mut inner = self.inner.lock().unwrap();
+            loop {
+                if let Some(v) = inner.buf.pop_front() {
+                    val = v;
+                    break;
+                }
+                if inner.num_senders == 0 {
+                    return Err(Error::Disconnected);
+                }
+                inner = self.recv_condvar.wait(inner).unwrap();
+            }
+        }
+        self.send_condvar.notify_one();
+        self.notify_send_wait_queue();
+        Ok(val)
+    }
+
+    /// Returns whether `send_async` would not return `Full`.
+    fn writable(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.receiver_disconnected || inner.buf.len() < self.cap
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().buf.len()
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Packet<'a, T> { }
+
+unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
+    fn ready(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.num_senders == 0 || inner.buf.len() > 0
+    }
+
+    fn is_disconnected(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.num_senders == 0 && inner.buf.len() == 0
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        let mut wait_queue = self.wait_queue.lock().unwrap();
+        if wait_queue.add(load) > 0 {
+            self.wait_queue_used.store(true, SeqCst);
+        }
+    }
+
+    fn unregister(&self, id: usize) {
+        let mut wait_queue = self.wait_queue.lock().unwrap();
+        if wait_queue.remove(id) == 0 {
+            self.wait_queue_used.store(false, SeqCst);
+        }
+    }
+}
+
+/// The send side of the channel. It has its own id and wait queue so that `Select` can
+/// wait for the channel to have space independently of waiting for messages.
+pub struct Writable<'a, T: Sendable+'a> {
+    pub packet: Arc<Packet<'a, T>>,
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Writable<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Writable<'a, T> { }
+
+unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Writable<'a, T> {
+    fn ready(&self) -> bool {
+        self.packet.writable()
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.packet.inner.lock().unwrap().receiver_disconnected
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        let mut wait_queue = self.packet.send_wait_queue.lock().unwrap();
+        if wait_queue.add(load) > 0 {
+            self.packet.send_wait_queue_used.store(true, SeqCst);
+        }
+    }
+
+    fn unregister(&self, id: usize) {
+        let mut wait_queue = self.packet.send_wait_queue.lock().unwrap();
+        if wait_queue.remove(id) == 0 {
+            self.packet.send_wait_queue_used.store(false, SeqCst);
+        }
+    }
+}
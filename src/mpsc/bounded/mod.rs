@@ -0,0 +1,170 @@
+//! A bounded MPSC channel.
+//!
+//! The buffer is protected by a mutex. See `bounded_fast` for a lock-free channel with
+//! the same API.
+
+use std::{fmt};
+
+use arc::{Arc, ArcTrait};
+use select::{Selectable, RecvSelectable, _Selectable};
+use {Error, Sendable};
+
+mod imp;
+#[cfg(test)] #[path = "../bounded_fast/test.rs"] mod test;
+
+#[cfg(test)]
+fn new_test<'a, T: Sendable+'a>(cap: usize) -> (Producer<'a, T>, Consumer<'a, T>) {
+    new(cap)
+}
+
+/// Creates a new bounded MPSC channel with capacity `cap`.
+///
+/// A capacity of `0` is treated as `1`.
+pub fn new<'a, T: Sendable+'a>(cap: usize) -> (Producer<'a, T>, Consumer<'a, T>) {
+    let packet = Arc::new(imp::Packet::new(cap));
+    packet.set_id(packet.unique_id());
+    let writable = Arc::new(imp::Writable { packet: packet.clone() });
+    packet.set_send_id(writable.unique_id());
+    (Producer { data: packet.clone(), writable: writable }, Consumer { data: packet })
+}
+
+/// A producer of a bounded MPSC channel.
+///
+/// The producer can be added to a `Select` object. It's ready when the buffer has space
+/// or the consumer has disconnected. All clones of a producer have the same id which is
+/// different from the id of the consumer.
+pub struct Producer<'a, T: Sendable+'a> {
+    data: Arc<imp::Packet<'a, T>>,
+    writable: Arc<imp::Writable<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> Producer<'a, T> {
+    /// Sends a message over the channel. Blocks if the channel is full.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The consumer has disconnected.
+    pub fn send_sync(&self, val: T) -> Result<(), (T, Error)> {
+        self.data.send_sync(val)
+    }
+
+    /// Sends a message over the channel. Does not block if the channel is full.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The consumer has disconnected.
+    /// - `Full` - The buffer is full.
+    pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
+        self.data.send_async(val)
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Producer<'a, T> { }
+
+impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
+    fn drop(&mut self) {
+        self.data.remove_sender();
+    }
+}
+
+impl<'a, T: Sendable+'a> Selectable<'a> for Producer<'a, T> {
+    fn id(&self) -> usize {
+        self.writable.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.writable.as_trait(&*self.writable as &(_Selectable+'a)) }
+    }
+}
+
+impl<'a, T: Sendable+'a> Clone for Producer<'a, T> {
+    fn clone(&self) -> Producer<'a, T> {
+        self.data.add_sender();
+        Producer { data: self.data.clone(), writable: self.writable.clone() }
+    }
+}
+
+/// A consumer of a bounded MPSC channel.
+pub struct Consumer<'a, T: Sendable+'a> {
+    data: Arc<imp::Packet<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> Consumer<'a, T> {
+    /// Receives a message from the channel. Blocks if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All producers have disconnected and the channel is empty.
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        self.data.recv_sync()
+    }
+
+    /// Receives a message over the channel. Does not block if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All producers have disconnected and the channel is empty.
+    /// - `Empty` - The buffer is empty.
+    pub fn recv_async(&self) -> Result<T, Error> {
+        self.data.recv_async()
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns whether all senders have disconnected and all messages have been
+    /// received.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
+
+impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
+    fn drop(&mut self) {
+        self.data.remove_receiver();
+    }
+}
+
+impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
+    fn id(&self) -> usize {
+        self.data.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
+    }
+}
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpsc::bounded::Producer")
+         .field("id", &self.writable.unique_id())
+         .field("len", &self.data.len())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpsc::bounded::Consumer")
+         .field("id", &self.data.unique_id())
+         .field("len", &self.data.len())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
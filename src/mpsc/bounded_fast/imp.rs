@@ -5,6 +5,7 @@ use std::sync::{Mutex, Condvar};
 use alloc::heap::{allocate, deallocate};
 use std::cell::{Cell};
 
+use arc::{Arc};
 use select::{_Selectable, WaitQueue, Payload};
 use alloc::{oom};
 use {Error, Sendable};
@@ -57,6 +58,10 @@ pub struct Packet<'a, T: Sendable+'a> {
     // Is any one selecting on this channel?
     wait_queue_used: AtomicBool,
     wait_queue: Mutex<WaitQueue<'a>>,
+
+    // Is any one selecting on the send side of this channel?
+    send_wait_queue_used: AtomicBool,
+    send_wait_queue: Mutex<WaitQueue<'a>>,
 }
 
 impl<'a, T: Sendable+'a> Packet<'a, T> {
@@ -90,6 +95,9 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
 
             wait_queue_used: AtomicBool::new(false),
             wait_queue: Mutex::new(WaitQueue::new()),
+
+            send_wait_queue_used: AtomicBool::new(false),
+            send_wait_queue: Mutex::new(WaitQueue::new()),
         };
         for i in 0..cap {
             packet.get_node(i).pos.store(i, SeqCst);
@@ -103,6 +111,11 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.wait_queue.lock().unwrap().set_id(id);
     }
 
+    /// Call this function before the send side is used with `Select`.
+    pub fn set_send_id(&self, id: usize) {
+        self.send_wait_queue.lock().unwrap().set_id(id);
+    }
+
     /// Call this function when the sender is cloned.
     pub fn add_sender(&self) {
         self.num_senders.fetch_add(1, SeqCst);
@@ -119,7 +132,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
-    fn notify_wait_queue(&self) {
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
         if self.wait_queue_used.load(SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -128,6 +142,16 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
+    /// Notifies everyone selecting on the send side of this channel.
+    pub fn notify_send_wait_queue(&self) {
+        if self.send_wait_queue_used.load(SeqCst) {
+            let mut wait_queue = self.send_wait_queue.lock().unwrap();
+            if wait_queue.notify() == 0 {
+                self.send_wait_queue_used.store(false, SeqCst);
+            }
+        }
+    }
+
     /// Call this function when the consumer is dropped.
     pub fn remove_receiver(&self) {
         self.receiver_disconnected.store(true, SeqCst);
@@ -135,6 +159,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         if self.sleeping_senders.load(SeqCst) > 0 {
             self.send_condvar.notify_all();
         }
+        self.notify_send_wait_queue();
     }
 
     fn get_node(&self, pos: usize) -> &mut Node<T> {
@@ -259,6 +284,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             }
         }
 
+        self.notify_send_wait_queue();
+
         Ok(val)
     }
 
@@ -284,6 +311,20 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
 
         rv
     }
+
+    /// Returns whether `send_async` would not return `Full`.
+    fn writable(&self) -> bool {
+        if self.receiver_disconnected.load(SeqCst) {
+            return true;
+        }
+        let next_write = self.next_write.load(SeqCst);
+        let node = self.get_node(next_write);
+        node.pos.load(SeqCst) as isize - next_write as isize >= 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap_mask + 1
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
@@ -311,6 +352,15 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         node.pos.load(SeqCst) as isize - 1 - next_read as isize >= 0
     }
 
+    fn is_disconnected(&self) -> bool {
+        if self.num_senders.load(SeqCst) != 0 {
+            return false;
+        }
+        let next_read = self.next_read.load(SeqCst);
+        let node = self.get_node(next_read);
+        node.pos.load(SeqCst) as isize - 1 - next_read as isize < 0
+    }
+
     fn register(&self, load: Payload<'a>) {
         let mut wait_queue = self.wait_queue.lock().unwrap();
         if wait_queue.add(load) > 0 {
@@ -325,3 +375,36 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         }
     }
 }
+
+/// The send side of the channel. It has its own id and wait queue so that `Select` can
+/// wait for the channel to have space independently of waiting for messages.
+pub struct Writable<'a, T: Sendable+'a> {
+    pub packet: Arc<Packet<'a, T>>,
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Writable<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Writable<'a, T> { }
+
+unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Writable<'a, T> {
+    fn ready(&self) -> bool {
+        self.packet.writable()
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.packet.receiver_disconnected.load(SeqCst)
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        let mut wait_queue = self.packet.send_wait_queue.lock().unwrap();
+        if wait_queue.add(load) > 0 {
+            self.packet.send_wait_queue_used.store(true, SeqCst);
+        }
+    }
+
+    fn unregister(&self, id: usize) {
+        let mut wait_queue = self.packet.send_wait_queue.lock().unwrap();
+        if wait_queue.remove(id) == 0 {
+            self.packet.send_wait_queue_used.store(false, SeqCst);
+        }
+    }
+}
@@ -1,28 +1,43 @@
 //! A bounded MPSC channel.
 
+use std::{fmt};
+
 use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
+use select::{Selectable, RecvSelectable, _Selectable};
 use {Error, Sendable};
 
 mod imp;
 #[cfg(test)] mod test;
 
+#[cfg(test)]
+fn new_test<'a, T: Sendable+'a>(cap: usize) -> (Producer<'a, T>, Consumer<'a, T>) {
+    unsafe { new(cap) }
+}
+
 /// Creates a new bounded MPSC channel with capacity at least `cap`.
 ///
 /// # Safety
 ///
 /// This is unsafe because under just the right circumstances this implementation can lead
 /// to undefined behavior. Note that these circumstances are extremely rare and almost
-/// impossible on 64 bit systems.
+/// impossible on 64 bit systems. `mpsc::bounded` is a slower alternative without this
+/// problem.
 pub unsafe fn new<'a, T: Sendable+'a>(cap: usize) -> (Producer<'a, T>, Consumer<'a, T>) {
     let packet = Arc::new(imp::Packet::new(cap));
     packet.set_id(packet.unique_id());
-    (Producer { data: packet.clone() }, Consumer { data: packet })
+    let writable = Arc::new(imp::Writable { packet: packet.clone() });
+    packet.set_send_id(writable.unique_id());
+    (Producer { data: packet.clone(), writable: writable }, Consumer { data: packet })
 }
 
 /// A producer of a bounded MPSC channel.
+///
+/// The producer can be added to a `Select` object. It's ready when the buffer has space
+/// or the consumer has disconnected. All clones of a producer have the same id which is
+/// different from the id of the consumer.
 pub struct Producer<'a, T: Sendable+'a> {
     data: Arc<imp::Packet<'a, T>>,
+    writable: Arc<imp::Writable<'a, T>>,
 }
 
 impl<'a, T: Sendable+'a> Producer<'a, T> {
@@ -54,14 +69,24 @@ impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
     }
 }
 
+impl<'a, T: Sendable+'a> Selectable<'a> for Producer<'a, T> {
+    fn id(&self) -> usize {
+        self.writable.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.writable.as_trait(&*self.writable as &(_Selectable+'a)) }
+    }
+}
+
 impl<'a, T: Sendable+'a> Clone for Producer<'a, T> {
     fn clone(&self) -> Producer<'a, T> {
         self.data.add_sender();
-        Producer { data: self.data.clone(), }
+        Producer { data: self.data.clone(), writable: self.writable.clone() }
     }
 }
 
-/// A consumer of a bounded SPMC channel.
+/// A consumer of a bounded MPSC channel.
 pub struct Consumer<'a, T: Sendable+'a> {
     data: Arc<imp::Packet<'a, T>>,
 }
@@ -85,6 +110,21 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn recv_async(&self) -> Result<T, Error> {
         self.data.recv_async(false)
     }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns whether all senders have disconnected and all messages have been
+    /// received.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
@@ -104,3 +144,27 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpsc::bounded_fast::Producer")
+         .field("id", &self.writable.unique_id())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpsc::bounded_fast::Consumer")
+         .field("id", &self.data.unique_id())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
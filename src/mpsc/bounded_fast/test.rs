@@ -1,4 +1,8 @@
+// These tests are shared by `mpsc::bounded` and `mpsc::bounded_fast`. `super::new_test`
+// creates a channel of the respective flavor.
+
 use std::thread::{self, sleep_ms};
+use std::time::{Duration};
 
 use select::{Select, Selectable};
 use {Error};
@@ -9,34 +13,34 @@ fn ms_sleep(ms: i64) {
 
 #[test]
 fn send_recv() {
-    let (send, recv) = unsafe { super::new(2) };
+    let (send, recv) = super::new_test(2);
     send.send_async(1u8).unwrap();
     assert_eq!(recv.recv_async().unwrap(), 1u8);
 }
 
 #[test]
 fn drop_send_recv() {
-    let (send, recv) = unsafe { super::new::<u8>(2) };
+    let (send, recv) = super::new_test::<u8>(2);
     drop(send);
     assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
 }
 
 #[test]
 fn drop_recv_send() {
-    let (send, recv) = unsafe { super::new(2) };
+    let (send, recv) = super::new_test(2);
     drop(recv);
     assert_eq!(send.send_async(1u8).unwrap_err(), (1, Error::Disconnected));
 }
 
 #[test]
 fn recv() {
-    let (_send, recv) = unsafe { super::new::<u8>(2) };
+    let (_send, recv) = super::new_test::<u8>(2);
     assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
 }
 
 #[test]
 fn sleep_send_recv() {
-    let (send, recv) = unsafe { super::new(2) };
+    let (send, recv) = super::new_test(2);
 
     thread::spawn(move || {
         ms_sleep(100);
@@ -48,7 +52,7 @@ fn sleep_send_recv() {
 
 #[test]
 fn send_sleep_recv() {
-    let (send, recv) = unsafe { super::new(2) };
+    let (send, recv) = super::new_test(2);
 
     thread::spawn(move || {
         send.send_async(1u8).unwrap();
@@ -60,7 +64,7 @@ fn send_sleep_recv() {
 
 #[test]
 fn send_sleep_recv_async() {
-    let (send, recv) = unsafe { super::new(2) };
+    let (send, recv) = super::new_test(2);
 
     thread::spawn(move || {
         send.send_async(1u8).unwrap();
@@ -72,7 +76,7 @@ fn send_sleep_recv_async() {
 
 #[test]
 fn send_5_recv_5() {
-    let (send, recv) = unsafe { super::new(4) };
+    let (send, recv) = super::new_test(4);
     send.send_async(1u8).unwrap();
     send.send_async(2u8).unwrap();
     send.send_async(3u8).unwrap();
@@ -89,7 +93,7 @@ fn multiple_producers(buf_size: usize) {
     const NUM: usize = 100;
     const RESULT: usize = (NUM*NUM-1)*(NUM*NUM)/2;
 
-    let (send, recv) = unsafe { super::new(buf_size) };
+    let (send, recv) = super::new_test(buf_size);
     let mut sum = 0;
     let mut threads = vec!();
     for i in 0..NUM {
@@ -130,7 +134,7 @@ fn multiple_producers_1000() {
 
 #[test]
 fn select_no_wait() {
-    let (send, recv) = unsafe { super::new(2) };
+    let (send, recv) = super::new_test(2);
 
     send.send_async(1u8).unwrap();
 
@@ -145,7 +149,7 @@ fn select_no_wait() {
 
 #[test]
 fn select_wait() {
-    let (send, recv) = unsafe { super::new(2) };
+    let (send, recv) = super::new_test(2);
 
     thread::spawn(move || {
         ms_sleep(100);
@@ -160,3 +164,60 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn select_send() {
+    let (send, recv) = super::new_test(2);
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2u8).unwrap();
+
+    let select = Select::new();
+    select.add(&send);
+
+    let mut buf = [0];
+    assert_eq!(select.wait_timeout(&mut buf, Some(Duration::from_millis(100))), None);
+
+    let send2 = send.clone();
+    assert_eq!(send2.id(), send.id());
+
+    let _t = thread::scoped(move || {
+        ms_sleep(100);
+        assert_eq!(recv.recv_sync().unwrap(), 1);
+        ms_sleep(100);
+    });
+
+    assert_eq!(select.wait(&mut buf), &mut [send.id()][..]);
+    send2.send_async(3).unwrap();
+}
+
+#[test]
+fn select_send_disconnect() {
+    let (send, recv) = super::new_test(2);
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2u8).unwrap();
+
+    let select = Select::new();
+    select.add(&send);
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(recv);
+    });
+
+    let mut buf = [0];
+    assert_eq!(select.wait(&mut buf), &mut [send.id()][..]);
+    assert_eq!(send.send_async(3).unwrap_err().1, Error::Disconnected);
+}
+
+#[test]
+fn is_disconnected() {
+    let (send, recv) = super::new_test(2);
+    let send2 = send.clone();
+    send.send_sync(1u8).unwrap();
+    drop(send);
+    assert!(!recv.is_disconnected());
+    drop(send2);
+    assert!(!recv.is_disconnected());
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert!(recv.is_disconnected());
+}
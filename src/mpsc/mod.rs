@@ -4,4 +4,5 @@
 //! be cloned.
 
 pub mod unbounded;
+pub mod bounded;
 pub mod bounded_fast;
@@ -120,3 +120,30 @@ fn async_comm(b: &mut Bencher) {
         }
     });
 }
+
+#[bench]
+fn reuse_stdlib(b: &mut Bencher) {
+    let (send, recv) = sync::mpsc::channel();
+    b.iter(|| {
+        for i in 0..128 {
+            send.send(i).unwrap();
+        }
+        while let Ok(num) = recv.try_recv() {
+            black_box(num);
+        }
+    });
+}
+
+#[bench]
+fn reuse_comm(b: &mut Bencher) {
+    // A long-lived channel so that the nodes freed by the receiver can be reused.
+    let (send, recv) = super::new();
+    b.iter(|| {
+        for i in 0..128 {
+            send.send(i).unwrap();
+        }
+        while let Ok(num) = recv.recv_async() {
+            black_box(num);
+        }
+    });
+}
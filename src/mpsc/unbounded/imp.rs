@@ -3,9 +3,13 @@ use std::sync::atomic::Ordering::{SeqCst};
 use std::sync::{Mutex, Condvar};
 use std::{mem, ptr};
 use std::cell::{Cell};
+use std::time::{Duration};
 
 use select::{_Selectable, WaitQueue, Payload};
-use {Error, Sendable};
+use {Error, Sendable, CancelToken};
+
+// The maximum number of unused nodes we keep around for later sends.
+const CACHE_SIZE: usize = 128;
 
 pub struct Packet<'a, T: Sendable+'a> {
     // The id of this channel. The address of the `arc::Inner` containing this channel.
@@ -17,6 +21,20 @@ pub struct Packet<'a, T: Sendable+'a> {
     // The next node we write to.
     write_end: AtomicPtr<Node<T>>,
 
+    // Stack of nodes the receiver no longer needs. The receiver pushes, the senders pop.
+    cache: AtomicPtr<Node<T>>,
+    // The number of nodes on the stack.
+    cache_len: AtomicUsize,
+    // Only one sender pops at a time. With concurrent pops the compare-and-swap in
+    // `alloc_node` could succeed even though the stack has changed in between (ABA).
+    // Pushes can't cause this since they never free a node that's on the stack.
+    cache_pop: Mutex<()>,
+
+    // The number of messages in the queue. Senders increment this before they link
+    // their messages so that it never underflows. It can therefore be larger than the
+    // number of messages the receiver can see.
+    num_queued: AtomicUsize,
+
     // The number of senders.
     num_senders: AtomicUsize,
     // Do we still have a receiver?
@@ -61,6 +79,12 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             read_end:  AtomicPtr::new(ptr),
             write_end: AtomicPtr::new(ptr),
 
+            cache: AtomicPtr::new(ptr::null_mut()),
+            cache_len: AtomicUsize::new(0),
+            cache_pop: Mutex::new(()),
+
+            num_queued: AtomicUsize::new(0),
+
             num_senders: AtomicUsize::new(1),
             have_receiver: AtomicBool::new(true),
 
@@ -92,7 +116,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
-    fn notify_wait_queue(&self) {
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
         if self.wait_queue_used.load(SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -101,6 +126,44 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
+    // Returns an empty node, either from the cache or a newly allocated one.
+    fn alloc_node(&self) -> *mut Node<T> {
+        // If another sender is popping right now we don't wait for it.
+        if let Ok(_guard) = self.cache_pop.try_lock() {
+            let mut head = self.cache.load(SeqCst);
+            while !head.is_null() {
+                let next = unsafe { (*head).next.load(SeqCst) };
+                let old = self.cache.compare_and_swap(head, next, SeqCst);
+                if old == head {
+                    self.cache_len.fetch_sub(1, SeqCst);
+                    unsafe { (*head).next.store(ptr::null_mut(), SeqCst); }
+                    return head;
+                }
+                head = old;
+            }
+        }
+        Node::new()
+    }
+
+    // Puts a node the receiver no longer needs into the cache or frees it if the cache
+    // is full. The value must already have been moved out of the node.
+    fn free_node(&self, node: *mut Node<T>) {
+        if self.cache_len.load(SeqCst) >= CACHE_SIZE {
+            unsafe { mem::transmute::<_, Box<Node<T>>>(node); }
+            return;
+        }
+        self.cache_len.fetch_add(1, SeqCst);
+        let mut head = self.cache.load(SeqCst);
+        loop {
+            unsafe { (*node).next.store(head, SeqCst); }
+            let old = self.cache.compare_and_swap(head, node, SeqCst);
+            if old == head {
+                return;
+            }
+            head = old;
+        }
+    }
+
     /// Call this when you drop the receiver.
     pub fn remove_receiver(&self) {
         self.have_receiver.store(false, SeqCst);
@@ -114,20 +177,70 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
-    pub fn send(&self, val: T) -> Result<(), (T, Error)> {
+    /// Appends the message without notifying anyone.
+    fn push(&self, val: T) -> Result<(), (T, Error)> {
         // If the receiver has been dropped we don't even try.
         if !self.have_receiver.load(SeqCst) {
             return Err((val, Error::Disconnected));
         }
 
         // Now this scales right up.
-        let new_end = Node::new();
+        let new_end = self.alloc_node();
+        self.num_queued.fetch_add(1, SeqCst);
         let write_end = self.write_end.swap(new_end, SeqCst);
         unsafe {
             (*write_end).val = Some(val);
             (*write_end).next.store(new_end, SeqCst);
         }
 
+        Ok(())
+    }
+
+    pub fn send(&self, val: T) -> Result<(), (T, Error)> {
+        try!(self.push(val));
+
+        self.notify_sleeping();
+
+        self.notify_wait_queue();
+
+        Ok(())
+    }
+
+    pub fn send_many(&self, vals: Vec<T>) -> Result<(), (Vec<T>, Error)> {
+        if !self.have_receiver.load(SeqCst) {
+            return Err((vals, Error::Disconnected));
+        }
+
+        let mut vals = vals.into_iter();
+        let first = match vals.next() {
+            Some(v) => v,
+            _ => return Ok(()),
+        };
+
+        // Build the chain for all but the first message. Nobody else can see these nodes
+        // until we link them into the queue below. `tail` is the new empty end.
+        let head = self.alloc_node();
+        let mut tail = head;
+        let mut count = 1;
+        for val in vals {
+            let new_end = self.alloc_node();
+            unsafe {
+                (*tail).val = Some(val);
+                (*tail).next.store(new_end, SeqCst);
+            }
+            tail = new_end;
+            count += 1;
+        }
+        self.num_queued.fetch_add(count, SeqCst);
+
+        // Like in `push` but the first message goes into the old end which we then link
+        // to the whole chain.
+        let write_end = self.write_end.swap(tail, SeqCst);
+        unsafe {
+            (*write_end).val = Some(first);
+            (*write_end).next.store(head, SeqCst);
+        }
+
         self.notify_sleeping();
 
         self.notify_wait_queue();
@@ -135,6 +248,20 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         Ok(())
     }
 
+    pub fn send_urgent(&self, val: T) -> Result<(), (T, Error)> {
+        try!(self.push(val));
+
+        // Don't trust `have_sleeping` and always go through the mutex.
+        {
+            let _guard = self.sleeping_mutex.lock().unwrap();
+            self.sleeping_condvar.notify_all();
+        }
+
+        self.notify_wait_queue();
+
+        Ok(())
+    }
+
     pub fn recv_async(&self) -> Result<T, Error> {
         let read_end = unsafe { &mut *self.read_end.load(SeqCst) };
         let next = read_end.next.load(SeqCst);
@@ -146,8 +273,32 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             };
         }
         self.read_end.store(next, SeqCst);
-        let mut node = unsafe { mem::transmute::<_, Box<Node<T>>>(read_end) };
-        Ok(node.val.take().unwrap())
+        let val = read_end.val.take().unwrap();
+        self.free_node(read_end);
+        self.num_queued.fetch_sub(1, SeqCst);
+        Ok(val)
+    }
+
+    pub fn recv_all(&self, buf: &mut Vec<T>) -> usize {
+        // Like `recv_async` in a loop. `ready` and `is_drained` dereference the read end
+        // from other threads, so we have to publish the next node before the old one
+        // goes back into the cache. Nodes appended while we're draining are picked up as
+        // soon as their `next` pointer becomes visible.
+        let mut read_end = self.read_end.load(SeqCst);
+        let mut count = 0;
+        loop {
+            let next = unsafe { (*read_end).next.load(SeqCst) };
+            if next.is_null() {
+                break;
+            }
+            self.read_end.store(next, SeqCst);
+            buf.push(unsafe { (*read_end).val.take().unwrap() });
+            self.free_node(read_end);
+            read_end = next;
+            count += 1;
+        }
+        self.num_queued.fetch_sub(count, SeqCst);
+        count
     }
 
     pub fn recv_sync(&self) -> Result<T, Error> {
@@ -171,6 +322,83 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.have_sleeping.store(false, SeqCst);
         rv
     }
+
+    /// Like `recv_sync` but also returns the number of messages still queued.
+    pub fn recv_sync_with_backlog(&self) -> Result<(T, usize), Error> {
+        let val = try!(self.recv_sync());
+        Ok((val, self.num_queued.load(SeqCst)))
+    }
+
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        match self.recv_async() {
+            v @ Ok(..) => return v,
+            Err(Error::Empty) => { },
+            e => return e,
+        }
+
+        let mut rv = None;
+        let guard = self.sleeping_mutex.lock().unwrap();
+        self.have_sleeping.store(true, SeqCst);
+        let _guard = self.sleeping_condvar.wait_timeout_with(guard, dur, |_| {
+            match self.recv_async() {
+                Err(Error::Empty) => false,
+                v => { rv = Some(v); true },
+            }
+        }).unwrap().0;
+        self.have_sleeping.store(false, SeqCst);
+        rv.unwrap_or(Err(Error::Empty))
+    }
+
+    pub fn recv_cancellable(&self, token: &CancelToken) -> Result<T, Error> {
+        // See `wake_all`.
+        let rv;
+        let mut guard = self.sleeping_mutex.lock().unwrap();
+        self.have_sleeping.store(true, SeqCst);
+        loop {
+            match self.recv_async() {
+                v @ Ok(..) => { rv = v; break; }
+                Err(Error::Empty) => { },
+                e => { rv = e; break; }
+            }
+            if token.is_cancelled() {
+                rv = Err(Error::Cancelled);
+                break;
+            }
+            guard = self.sleeping_condvar.wait(guard).unwrap();
+        }
+        self.have_sleeping.store(false, SeqCst);
+        rv
+    }
+
+    /// Wakes up the receiver if it's sleeping. Since we acquire the mutex, a receiver that
+    /// checks a condition before going to sleep while holding the mutex either sees a
+    /// change made before this call or is woken up.
+    pub fn wake_all(&self) {
+        let _guard = self.sleeping_mutex.lock().unwrap();
+        self.sleeping_condvar.notify_all();
+    }
+
+    pub fn is_drained(&self) -> bool {
+        // Check the senders first so that we see all messages sent before the last
+        // sender disconnected.
+        if self.num_senders.load(SeqCst) != 0 {
+            return false;
+        }
+        let read_end = unsafe { &*self.read_end.load(SeqCst) };
+        read_end.next.load(SeqCst).is_null()
+    }
+
+    pub fn has_sleeping(&self) -> bool {
+        self.have_sleeping.load(SeqCst)
+    }
+
+    pub fn sender_count(&self) -> usize {
+        self.num_senders.load(SeqCst)
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.have_receiver.load(SeqCst) as usize
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
@@ -180,6 +408,11 @@ impl<'a, T: Sendable+'a> Drop for Packet<'a, T> {
     fn drop(&mut self) {
         while self.recv_async().is_ok() { }
         unsafe { ptr::read(self.read_end.load(SeqCst)); }
+        let mut node = self.cache.load(SeqCst);
+        while !node.is_null() {
+            let node_box = unsafe { mem::transmute::<_, Box<Node<T>>>(node) };
+            node = node_box.next.load(SeqCst);
+        }
     }
 }
 
@@ -192,6 +425,10 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         !read_end.next.load(SeqCst).is_null()
     }
 
+    fn is_disconnected(&self) -> bool {
+        self.is_drained()
+    }
+
     fn register(&self, load: Payload<'a>) {
         let mut wait_queue = self.wait_queue.lock().unwrap();
         if wait_queue.add(load) > 0 {
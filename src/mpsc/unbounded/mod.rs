@@ -2,9 +2,14 @@
 //!
 //! See the unbounded SPSC docs.
 
+use std::sync::atomic::{AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst};
+use std::time::{Duration};
+use std::{fmt};
+
 use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
-use {Error, Sendable};
+use select::{Selectable, RecvSelectable, _Selectable};
+use {Error, Sendable, TryRecvResult, CancelToken};
 
 mod imp;
 #[cfg(test)] mod test;
@@ -14,12 +19,28 @@ mod imp;
 pub fn new<'a, T: Sendable+'a>() -> (Producer<'a, T>, Consumer<'a, T>) {
     let packet = Arc::new(imp::Packet::new());
     packet.set_id(packet.unique_id());
-    (Producer { data: packet.clone() }, Consumer { data: packet })
+    (Producer { data: packet.clone(), closed: AtomicBool::new(false) },
+     Consumer { data: packet })
+}
+
+/// Creates a new unbounded MPSC channel with `n` producers.
+///
+/// If `n` is `0`, the channel is disconnected from the start.
+pub fn new_fan_in<'a, T: Sendable+'a>(n: usize)
+                                      -> (Vec<Producer<'a, T>>, Consumer<'a, T>) {
+    let (send, recv) = new();
+    let mut senders: Vec<_> = (1..n).map(|_| send.clone()).collect();
+    if n > 0 {
+        senders.push(send);
+    }
+    (senders, recv)
 }
 
 /// The producing end of an unbounded MPSC channel.
 pub struct Producer<'a, T: Sendable+'a> {
     data: Arc<imp::Packet<'a, T>>,
+    // Has this handle been closed?
+    closed: AtomicBool,
 }
 
 impl<'a, T: Sendable+'a> Producer<'a, T> {
@@ -27,22 +48,92 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     ///
     /// ### Error
     ///
-    /// - `Disconnected` - The receiver has disconnected.
+    /// - `Disconnected` - The receiver has disconnected or this producer has been closed.
     pub fn send(&self, val: T) -> Result<(), (T, Error)> {
+        if self.closed.load(SeqCst) {
+            return Err((val, Error::Disconnected));
+        }
         self.data.send(val)
     }
+
+    /// Appends all messages in `vals` to the channel at once. The messages are received
+    /// in order and no messages from other senders are interleaved.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected or this producer has been closed.
+    ///   No message has been sent.
+    pub fn send_many(&self, vals: Vec<T>) -> Result<(), (Vec<T>, Error)> {
+        if self.closed.load(SeqCst) {
+            return Err((vals, Error::Disconnected));
+        }
+        self.data.send_many(vals)
+    }
+
+    /// Appends a message to the channel and unconditionally wakes up the receiver.
+    ///
+    /// Unlike `send`, this always acquires the receiver's mutex to wake it up, even if
+    /// the receiver doesn't appear to be sleeping. Use this for control messages that
+    /// have to be delivered with low latency.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected or this producer has been closed.
+    pub fn send_urgent(&self, val: T) -> Result<(), (T, Error)> {
+        if self.closed.load(SeqCst) {
+            return Err((val, Error::Disconnected));
+        }
+        self.data.send_urgent(val)
+    }
+
+    /// Returns whether the receiver is currently blocked in `recv_sync`.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn has_blocked_receiver(&self) -> bool {
+        self.data.has_sleeping()
+    }
+
+    /// Returns the number of producers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn sender_count(&self) -> usize {
+        self.data.sender_count()
+    }
+
+    /// Returns the number of consumers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn receiver_count(&self) -> usize {
+        self.data.receiver_count()
+    }
+
+    /// Disconnects this producer as if it had been dropped.
+    ///
+    /// Afterwards all attempts to send over this producer fail with `Disconnected`. Once
+    /// all producers have been closed or dropped, the consumer sees the channel as
+    /// disconnected after it has received the remaining messages. Closing a producer
+    /// more than once has no effect.
+    pub fn close(&self) {
+        if !self.closed.swap(true, SeqCst) {
+            self.data.remove_sender();
+        }
+    }
 }
 
 impl<'a, T: Sendable+'a> Clone for Producer<'a, T> {
+    // The clone of a closed producer is closed as well.
     fn clone(&self) -> Producer<'a, T> {
-        self.data.add_sender();
-        Producer { data: self.data.clone() }
+        let closed = self.closed.load(SeqCst);
+        if !closed {
+            self.data.add_sender();
+        }
+        Producer { data: self.data.clone(), closed: AtomicBool::new(closed) }
     }
 }
 
 impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
     fn drop(&mut self) {
-        self.data.remove_sender()
+        self.close();
     }
 }
 
@@ -72,6 +163,131 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn recv_async(&self) -> Result<T, Error> {
         self.data.recv_async()
     }
+
+    /// Receives all messages that are currently available and appends them to `buf`.
+    /// Does not block.
+    ///
+    /// Returns the number of messages appended.
+    pub fn recv_all(&self, buf: &mut Vec<T>) -> usize {
+        self.data.recv_all(buf)
+    }
+
+    /// Receives a message from this channel. Does not block if no message is available.
+    ///
+    /// This is like `recv_async` except that the two cases in which no message is
+    /// available are reported as distinct variants of `TryRecvResult`.
+    pub fn try_recv(&self) -> TryRecvResult<T> {
+        match self.data.recv_async() {
+            Ok(v) => TryRecvResult::Ok(v),
+            Err(Error::Empty) => TryRecvResult::Empty,
+            Err(Error::Disconnected) => TryRecvResult::Disconnected,
+            Err(e) => unreachable!("recv_async returned {:?}", e),
+        }
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns whether the senders have disconnected and all messages have been
+    /// received.
+    ///
+    /// If this returns `true`, every further receive operation returns `Disconnected`.
+    pub fn is_drained(&self) -> bool {
+        self.data.is_drained()
+    }
+
+    /// Turns this consumer into a consumer that calls `f` on every message before it
+    /// returns it.
+    pub fn inspect<F: Fn(&T)>(self, f: F) -> InspectConsumer<'a, T, F> {
+        InspectConsumer { data: self, f: f }
+    }
+
+    /// Receives a message from the channel like `recv_sync` and also returns the number
+    /// of messages that were still queued after it had been removed.
+    ///
+    /// The number is read right after the receive. It includes messages whose senders
+    /// have not finished sending them, so the next `recv_async` can return `Empty` even
+    /// if the number is not `0`. It is meant as a hint, e.g., to decide whether to
+    /// receive messages in batches.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The channel is empty and all senders have disconnected.
+    pub fn recv_sync_with_backlog(&self) -> Result<(T, usize), Error> {
+        self.data.recv_sync_with_backlog()
+    }
+
+    /// Receives a message from the channel. Blocks for at most `dur` if the channel is
+    /// empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The channel is empty and all senders have disconnected.
+    /// - `Empty` - The channel was still empty when the timeout expired.
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        self.data.recv_timeout(dur)
+    }
+
+    /// Creates a token that can cancel `recv_cancellable` calls on this consumer from
+    /// another thread.
+    pub fn cancel_token(&self) -> CancelToken<'a> {
+        let data = self.data.clone();
+        CancelToken::new(self.data.unique_id(), move || data.wake_all())
+    }
+
+    /// Receives a message from the channel. Blocks until a message is available or
+    /// `token` has been cancelled.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The channel is empty and all senders have disconnected.
+    /// - `Cancelled` - The channel is empty and `token` has been cancelled.
+    ///
+    /// ### Panic
+    ///
+    /// Panics if `token` wasn't created by this consumer.
+    pub fn recv_cancellable(&self, token: &CancelToken) -> Result<T, Error> {
+        assert!(token.id() == self.data.unique_id(), "token belongs to another channel");
+        self.data.recv_cancellable(token)
+    }
+
+    /// Returns the number of producers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn sender_count(&self) -> usize {
+        self.data.sender_count()
+    }
+
+    /// Returns the number of consumers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn receiver_count(&self) -> usize {
+        self.data.receiver_count()
+    }
+
+    /// Returns whether all senders have disconnected and all messages have been
+    /// received.
+    ///
+    /// This is the same as `is_drained`. Unlike the readiness reported to `Select`
+    /// objects, this is `false` as long as messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.is_drained()
+    }
+
+    /// Returns the number of strong references to the channel's shared state, including
+    /// the one held by this consumer.
+    ///
+    /// The state is freed once all of these references are gone. Every `CancelToken`
+    /// created by `cancel_token` holds one. `Select` objects only hold weak references
+    /// to registered consumers, so a registration doesn't count towards this number
+    /// except while a `Select` is checking the consumer's readiness. The result is only
+    /// a snapshot and might be outdated by the time it's returned.
+    pub fn strong_refs(&self) -> usize {
+        self.data.strong_count()
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
@@ -91,3 +307,87 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+/// A consumer that calls a function on every message it receives.
+///
+/// See `Consumer::inspect`.
+pub struct InspectConsumer<'a, T: Sendable+'a, F: Fn(&T)> {
+    data: Consumer<'a, T>,
+    f: F,
+}
+
+impl<'a, T: Sendable+'a, F: Fn(&T)> InspectConsumer<'a, T, F> {
+    /// Receives a message from the channel. Blocks if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The channel is empty and all senders have disconnected.
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        let val = try!(self.data.recv_sync());
+        (self.f)(&val);
+        Ok(val)
+    }
+
+    /// Receives a message from the channel. Does not block if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The channel is empty and all senders have disconnected.
+    /// - `Empty` - The channel is empty.
+    pub fn recv_async(&self) -> Result<T, Error> {
+        let val = try!(self.data.recv_async());
+        (self.f)(&val);
+        Ok(val)
+    }
+
+    /// Returns the underlying consumer.
+    pub fn into_inner(self) -> Consumer<'a, T> {
+        self.data
+    }
+}
+
+impl<'a, T: Sendable+'a, F: Fn(&T)> Selectable<'a> for InspectConsumer<'a, T, F> {
+    fn id(&self) -> usize {
+        self.data.id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        self.data.as_selectable()
+    }
+}
+
+impl<'a, T: Sendable+'a, F: Fn(&T)> RecvSelectable<'a, T> for InspectConsumer<'a, T, F> {
+    fn recv_async(&self) -> Result<T, Error> {
+        InspectConsumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpsc::unbounded::Producer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpsc::unbounded::Consumer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a, F: Fn(&T)> fmt::Debug for InspectConsumer<'a, T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("mpsc::unbounded::InspectConsumer")
+         .field("id", &self.data.data.unique_id())
+         .finish()
+    }
+}
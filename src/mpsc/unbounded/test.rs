@@ -1,10 +1,11 @@
 use std::sync::{Arc};
 use std::thread::{self, sleep_ms};
-use std::sync::atomic::{AtomicUsize};
+use std::sync::atomic::{AtomicUsize, AtomicBool};
 use std::sync::atomic::Ordering::{SeqCst};
+use std::time::{Duration};
 
 use select::{Select, Selectable};
-use {Error};
+use {Error, TryRecvResult};
 
 fn ms_sleep(ms: i64) {
     sleep_ms(ms as u32);
@@ -37,6 +38,16 @@ fn recv() {
     assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
 }
 
+#[test]
+fn try_recv() {
+    let (send, recv) = super::new::<u8>();
+    assert_eq!(recv.try_recv(), TryRecvResult::Empty);
+    send.send(1).unwrap();
+    assert_eq!(recv.try_recv(), TryRecvResult::Ok(1));
+    drop(send);
+    assert_eq!(recv.try_recv(), TryRecvResult::Disconnected);
+}
+
 #[test]
 fn sleep_send_recv() {
     let (send, recv) = super::new();
@@ -146,3 +157,306 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn fan_in() {
+    let (sends, recv) = super::new_fan_in(3);
+    assert_eq!(sends.len(), 3);
+    for (i, send) in sends.iter().enumerate() {
+        send.send(i as u8).unwrap();
+    }
+    drop(sends);
+    assert_eq!(recv.recv_sync().unwrap(), 0);
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn fan_in_zero() {
+    let (sends, recv) = super::new_fan_in::<u8>(0);
+    assert!(sends.is_empty());
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn is_drained() {
+    let (send, recv) = super::new();
+    send.send(1u8).unwrap();
+    send.send(2u8).unwrap();
+    assert!(!recv.is_drained());
+    drop(send);
+    assert!(!recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert!(!recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap(), 2);
+    assert!(recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn send_urgent() {
+    let (send, recv) = super::new();
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send_urgent(1u8).unwrap();
+    });
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+}
+
+#[test]
+fn send_urgent_disconnected() {
+    let (send, recv) = super::new();
+    drop(recv);
+    assert_eq!(send.send_urgent(1u8).unwrap_err(), (1, Error::Disconnected));
+}
+
+#[test]
+fn has_blocked_receiver() {
+    let (send, recv) = super::new();
+    assert!(!send.has_blocked_receiver());
+    thread::spawn(move || {
+        assert_eq!(recv.recv_sync().unwrap(), 1u8);
+    });
+    ms_sleep(100);
+    assert!(send.has_blocked_receiver());
+    send.send(1).unwrap();
+}
+
+#[test]
+fn inspect() {
+    use std::cell::{RefCell};
+
+    let seen = RefCell::new(vec!());
+    let (send, recv) = super::new();
+    let recv = recv.inspect(|v: &u8| seen.borrow_mut().push(*v));
+    for i in 0..4 {
+        send.send(i).unwrap();
+    }
+    drop(send);
+    assert_eq!(recv.recv_async().unwrap(), 0);
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+    assert_eq!(recv.recv_async().unwrap(), 3);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+    assert_eq!(*seen.borrow(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn recv_timeout() {
+    let (send, recv) = super::new();
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send(1u8).unwrap();
+    });
+    assert_eq!(recv.recv_timeout(Duration::from_millis(10)).unwrap_err(), Error::Empty);
+    assert_eq!(recv.recv_timeout(Duration::from_millis(1000)).unwrap(), 1);
+    assert_eq!(recv.recv_timeout(Duration::from_millis(1000)).unwrap_err(),
+               Error::Disconnected);
+}
+
+#[test]
+fn send_many() {
+    let (send, recv) = super::new();
+    send.send(0usize).unwrap();
+    send.send_many((1..1001).collect()).unwrap();
+    send.send_many(vec!()).unwrap();
+    send.send(1001).unwrap();
+    for i in 0..1002 {
+        assert_eq!(recv.recv_async().unwrap(), i);
+    }
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
+    drop(recv);
+    assert_eq!(send.send_many(vec!(1, 2)).unwrap_err(), (vec!(1, 2), Error::Disconnected));
+}
+
+#[test]
+fn recv_all() {
+    let (send, recv) = super::new();
+    let mut buf = vec!();
+    assert_eq!(recv.recv_all(&mut buf), 0);
+    send.send(0usize).unwrap();
+    send.send(1).unwrap();
+    assert_eq!(recv.recv_all(&mut buf), 2);
+    assert_eq!(recv.recv_all(&mut buf), 0);
+    send.send(2).unwrap();
+    assert_eq!(recv.recv_all(&mut buf), 1);
+    assert_eq!(buf, vec!(0, 1, 2));
+}
+
+#[test]
+fn recv_all_concurrent() {
+    const NUM: usize = 10000;
+
+    let (send, recv) = super::new();
+    let _t = thread::scoped(move || {
+        for i in 0..NUM {
+            send.send(i).unwrap();
+        }
+    });
+    let mut buf = vec!();
+    loop {
+        recv.recv_all(&mut buf);
+        if recv.is_drained() {
+            break;
+        }
+        thread::yield_now();
+    }
+    assert_eq!(buf, (0..NUM).collect::<Vec<_>>());
+}
+
+#[test]
+fn recv_all_while_selecting() {
+    const NUM: usize = 10000;
+
+    let (send, recv) = super::new();
+    let select = Select::new();
+    select.add(&recv);
+    let done = AtomicBool::new(false);
+    let _s = {
+        let select = &select;
+        let done = &done;
+        thread::scoped(move || {
+            let mut ready = [0];
+            while !done.load(SeqCst) {
+                select.wait_timeout(&mut ready, Some(Duration::from_millis(10)));
+            }
+        })
+    };
+    let _t = thread::scoped(move || {
+        for i in 0..NUM {
+            send.send(i).unwrap();
+        }
+    });
+    let mut buf = vec!();
+    while buf.len() < NUM {
+        recv.recv_all(&mut buf);
+        thread::yield_now();
+    }
+    done.store(true, SeqCst);
+    assert_eq!(buf, (0..NUM).collect::<Vec<_>>());
+}
+
+#[test]
+fn sender_count() {
+    let (send, recv) = super::new::<u8>();
+    assert_eq!(send.sender_count(), 1);
+    let send2 = send.clone();
+    assert_eq!(recv.sender_count(), 2);
+    drop(send2);
+    assert_eq!(recv.sender_count(), 1);
+    assert_eq!(send.receiver_count(), 1);
+    drop(recv);
+    assert_eq!(send.receiver_count(), 0);
+}
+
+#[test]
+fn reuse_nodes() {
+    const NUM: usize = 1000;
+
+    let (send, recv) = super::new();
+    let send2 = send.clone();
+    let _t = thread::scoped(move || {
+        for i in 0..NUM {
+            send2.send(i).unwrap();
+        }
+    });
+    for i in 0..NUM {
+        send.send(NUM + i).unwrap();
+    }
+    drop(send);
+    let mut first = 0;
+    let mut second = NUM;
+    while let Ok(n) = recv.recv_sync() {
+        if n < NUM {
+            assert_eq!(n, first);
+            first += 1;
+        } else {
+            assert_eq!(n, second);
+            second += 1;
+        }
+    }
+    assert_eq!((first, second), (NUM, 2 * NUM));
+}
+
+#[test]
+fn close() {
+    let (send, recv) = super::new();
+    let send2 = send.clone();
+    send.send(1u8).unwrap();
+    send.close();
+    send.close();
+    assert_eq!(send.send(2).unwrap_err(), (2, Error::Disconnected));
+    assert_eq!(recv.sender_count(), 1);
+    let send3 = send.clone();
+    assert_eq!(recv.sender_count(), 1);
+    send2.send(3).unwrap();
+    drop(send2);
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap(), 3);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+    drop(send);
+    drop(send3);
+}
+
+#[test]
+fn recv_cancellable() {
+    let (send, recv) = super::new();
+    let token = recv.cancel_token();
+    send.send(1u8).unwrap();
+    assert_eq!(recv.recv_cancellable(&token).unwrap(), 1);
+
+    let token2 = token.clone();
+    thread::spawn(move || {
+        ms_sleep(100);
+        token2.cancel();
+    });
+    assert_eq!(recv.recv_cancellable(&token).unwrap_err(), Error::Cancelled);
+    assert!(token.is_cancelled());
+
+    // Messages are still received after the token has been cancelled and the channel
+    // is still connected.
+    send.send(2).unwrap();
+    assert_eq!(recv.recv_cancellable(&token).unwrap(), 2);
+    assert_eq!(recv.recv_cancellable(&token).unwrap_err(), Error::Cancelled);
+    send.send(3).unwrap();
+    assert_eq!(recv.recv_sync().unwrap(), 3);
+}
+
+#[test]
+fn strong_refs() {
+    let (send, recv) = super::new::<u8>();
+    assert_eq!(recv.strong_refs(), 2);
+    let send2 = send.clone();
+    assert_eq!(recv.strong_refs(), 3);
+    let token = recv.cancel_token();
+    assert_eq!(recv.strong_refs(), 4);
+    drop(token);
+    drop(send2);
+
+    let select = Select::new();
+    select.add(&recv);
+    assert_eq!(recv.strong_refs(), 2);
+    select.remove(&recv);
+    drop(send);
+    assert_eq!(recv.strong_refs(), 1);
+}
+
+#[test]
+fn recv_sync_with_backlog() {
+    let (send, recv) = super::new();
+    send.send(0u8).unwrap();
+    send.send_many(vec!(1, 2, 3)).unwrap();
+    send.send_urgent(4).unwrap();
+    for i in 0..5 {
+        assert_eq!(recv.recv_sync_with_backlog().unwrap(), (i, 4 - i as usize));
+    }
+
+    send.send_many(vec!(5, 6, 7)).unwrap();
+    let mut buf = vec!();
+    assert_eq!(recv.recv_all(&mut buf), 3);
+    send.send(8).unwrap();
+    assert_eq!(recv.recv_sync_with_backlog().unwrap(), (8, 0));
+    drop(send);
+    assert_eq!(recv.recv_sync_with_backlog().unwrap_err(), Error::Disconnected);
+}
@@ -0,0 +1,78 @@
+//! Adapters that make foreign channels selectable.
+
+use std::sync::{mpsc};
+use std::{thread};
+
+use arc::{ArcTrait};
+use spsc::{unbounded};
+use super::{Selectable, RecvSelectable, _Selectable};
+use {Error};
+
+/// A `Selectable` wrapper around the receiving end of a `std::sync::mpsc` channel.
+///
+/// The standard library channels can't notify `Select` objects. Therefore a helper
+/// thread blocks on the wrapped receiver and forwards every message into an unbounded
+/// SPSC channel which is what the `Select` object actually waits on. This has the
+/// following consequences:
+///
+/// - Every message passes through a second channel and wakes up a second thread.
+/// - The messages are removed from the standard library channel as soon as possible,
+///   regardless of how fast they are received from the wrapper.
+/// - The helper thread only notices that the wrapper has been dropped when it tries to
+///   forward the next message or the standard library channel disconnects. Until then
+///   it keeps running.
+pub struct StdReceiver<T: Send+'static> {
+    data: unbounded::Consumer<'static, T>,
+}
+
+impl<T: Send+'static> StdReceiver<T> {
+    /// Wraps `recv` and spawns the helper thread.
+    pub fn new(recv: mpsc::Receiver<T>) -> StdReceiver<T> {
+        let (send, data) = unbounded::new();
+        thread::spawn(move || {
+            while let Ok(val) = recv.recv() {
+                if send.send(val).is_err() {
+                    break;
+                }
+            }
+        });
+        StdReceiver { data: data }
+    }
+
+    /// Receives a message. Blocks if no message is available.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All senders of the wrapped channel have disconnected and all
+    ///   messages have been received.
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        self.data.recv_sync()
+    }
+
+    /// Receives a message. Does not block if no message is available.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All senders of the wrapped channel have disconnected and all
+    ///   messages have been received.
+    /// - `Empty` - No message is available.
+    pub fn recv_async(&self) -> Result<T, Error> {
+        self.data.recv_async()
+    }
+}
+
+impl<T: Send+'static> Selectable<'static> for StdReceiver<T> {
+    fn id(&self) -> usize {
+        self.data.id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'static>+'static> {
+        self.data.as_selectable()
+    }
+}
+
+impl<T: Send+'static> RecvSelectable<'static, T> for StdReceiver<T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        StdReceiver::recv_async(self)
+    }
+}
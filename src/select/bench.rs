@@ -0,0 +1,54 @@
+use test::{Bencher, black_box};
+
+use spsc::unbounded::{new};
+use super::{Select, Selectable};
+
+const NUM_TARGETS: usize = 1000;
+
+#[bench]
+fn add_remove_1000(b: &mut Bencher) {
+    let channels: Vec<_> = (0..NUM_TARGETS).map(|_| new::<u8>()).collect();
+    b.iter(|| {
+        let select = Select::new();
+        for &(_, ref recv) in &channels {
+            select.add(recv);
+        }
+        for &(_, ref recv) in &channels {
+            select.remove(recv);
+        }
+    });
+}
+
+#[bench]
+fn wait_1000(b: &mut Bencher) {
+    let channels: Vec<_> = (0..NUM_TARGETS).map(|_| new::<u8>()).collect();
+    let select = Select::new();
+    for &(_, ref recv) in &channels {
+        select.add(recv);
+    }
+    let mut buf = [0; NUM_TARGETS];
+    b.iter(|| {
+        for chunk in channels.chunks(10) {
+            chunk[0].0.send(1).unwrap();
+        }
+        black_box(select.wait(&mut buf).len());
+        for chunk in channels.chunks(10) {
+            chunk[0].1.recv_async().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn send_burst_1000(b: &mut Bencher) {
+    let (send, recv) = new::<u8>();
+    let select = Select::new();
+    select.add(&recv);
+    let mut buf = [0];
+    b.iter(|| {
+        for _ in 0..NUM_TARGETS {
+            send.send(1).unwrap();
+        }
+        black_box(select.wait(&mut buf).len());
+        while recv.recv_async().is_ok() { }
+    });
+}
@@ -1,62 +1,213 @@
-use std::collections::{HashMap};
-use std::hash::{Hash, Hasher};
-use std::sync::{Mutex, Condvar};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher, BuildHasherDefault};
+use std::sync::{Mutex, MutexGuard, Condvar, TryLockError};
+use std::sync::atomic::{AtomicUsize};
+use std::sync::atomic::Ordering::{SeqCst};
 use std::cmp::{self, Ordering};
 use std::time::{Duration};
-use std::{mem};
+use std::marker::{PhantomData};
+use std::{iter, mem};
 
-use arc::{Arc, Weak, WeakTrait};
+use arc::{Arc, ArcTrait, Weak, WeakTrait};
 use sortedvec::{SortedVec};
-use super::{Selectable, _Selectable};
+use super::{Selectable, RecvSelectable, _Selectable};
+use {Error};
+
+/// The status of a ready target as reported by `Select::wait_with_status`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// The target is ready.
+    Ready,
+    /// The target is ready only because its peer has disconnected. It will stay ready
+    /// forever and should usually be removed from the `Select` object.
+    Disconnected,
+}
+
+/// The order in which `Select::wait` reports ready targets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Policy {
+    /// Ready targets are reported in ascending order of their ids. If fewer slots than
+    /// ready targets are passed to `wait`, every call continues after the targets that
+    /// were reported by the previous call so that no ready target starves.
+    ///
+    /// Unlike with `RoundRobin`, the reported ids are always sorted and a call that has
+    /// room for all ready targets starts at the lowest id.
+    Lowest,
+    /// Ready targets are reported in ascending order of their ids, starting after the
+    /// target that was reported first by the previous call. The reported window wraps
+    /// around at the largest id.
+    RoundRobin,
+    /// Ready targets are reported in ascending order of their ids, starting at a
+    /// pseudo-random target. The same seed produces the same sequence of start positions.
+    Random {
+        seed: u64,
+    },
+}
+
+/// The result of `Select::poll`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PollResult<'b> {
+    /// The ids of the ready targets.
+    Ready(&'b mut [usize]),
+    /// No target is ready. `wait` would block.
+    WouldBlock,
+    /// The `Select` object contains no targets.
+    Empty,
+}
 
 /// Container for all targets being selected on.
 pub struct Select<'a> {
     condvar: Arc<Condvar>,
+    // See `Inner::generation`.
+    generation: Arc<AtomicUsize>,
     inner: Arc<Mutex<Inner<'a>>>,
 }
 
 impl<'a> Select<'a> {
     /// Creates a new `Select` object.
     pub fn new() -> Select<'a> {
+        Select::new_with_policy(Policy::Lowest)
+    }
+
+    /// Creates a new `Select` object that reports ready targets according to `policy`.
+    pub fn new_with_policy(policy: Policy) -> Select<'a> {
         let condvar = Arc::new(Condvar::new());
+        let generation = Arc::new(AtomicUsize::new(0));
+        let inner = Arc::new(Mutex::new(Inner::new(condvar.clone(), generation.clone(),
+                                                   policy)));
+        inner.lock().unwrap().parents.set_id(inner.unique_id());
         Select {
-            condvar: condvar.clone(),
-            inner: Arc::new(Mutex::new(Inner::new(condvar)))
+            condvar: condvar,
+            generation: generation,
+            inner: inner,
         }
     }
 
     fn as_payload(&self) -> Payload<'a> {
-        Payload { data: self.inner.downgrade() }
+        Payload {
+            data: self.inner.downgrade(),
+            generation: self.generation.clone(),
+            condvar: self.condvar.clone(),
+        }
     }
 
     /// Adds a target to the select object.
+    ///
+    /// Targets with the same id, e.g., clones of the same channel, are stored only once.
+    /// Adding such a target again increases a reference count and the target stays in
+    /// the `Select` object until it has been removed as often as it has been added.
     pub fn add<T: Selectable<'a>+'a>(&self, sel: &T) {
-        let sel = sel.as_selectable();
+        self.try_add(sel);
+    }
 
-        // Careful not to deadlock in `register`.
-        sel.register(self.as_payload());
+    /// Adds a target to the select object. Returns `false` if the target has already
+    /// been added, `true` otherwise. See `add` for what happens if the target has already
+    /// been added.
+    pub fn try_add<T: Selectable<'a>+'a>(&self, sel: &T) -> bool {
+        self.insert(sel, None, None)
+    }
 
-        let mut inner = self.inner.lock().unwrap();
+    /// Adds a target with a priority to the select object. If several targets are ready,
+    /// `wait` reports those with a higher priority first. Targets added with `add` have
+    /// priority `0`.
+    ///
+    /// If the target has already been added, its priority is updated. See `add`.
+    pub fn add_with_priority<T: Selectable<'a>+'a>(&self, sel: &T, prio: u32) {
+        self.insert(sel, Some(prio), None);
+    }
+
+    /// Adds a target with an application-defined key to the select object. `wait_keyed`
+    /// reports the keys of ready targets instead of their ids. Targets added without a
+    /// key use their id as key.
+    ///
+    /// If the target has already been added, its key is updated. See `add`.
+    pub fn add_keyed<T: Selectable<'a>+'a>(&self, sel: &T, key: u64) {
+        self.insert(sel, None, Some(key));
+    }
 
+    fn insert<T: Selectable<'a>+'a>(&self, sel: &T, prio: Option<u32>,
+                                    key: Option<u64>) -> bool {
+        let sel = sel.as_selectable();
         let id = sel.unique_id();
 
+        // Insert the entry first so that concurrent calls don't register the target
+        // twice.
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let updated = inner.wait_list.get_mut(&id).map(|entry| {
+                entry.refs += 1;
+                let old_prio = entry.priority;
+                if let Some(prio) = prio {
+                    entry.priority = prio;
+                }
+                if let Some(key) = key {
+                    entry.key = key;
+                }
+                (old_prio, entry.priority)
+            });
+            if let Some((old_prio, new_prio)) = updated {
+                match (old_prio > 0, new_prio > 0) {
+                    (false, true) => inner.prioritized += 1,
+                    (true, false) => inner.prioritized -= 1,
+                    _ => { },
+                }
+                return false;
+            }
+            if prio.unwrap_or(0) > 0 {
+                inner.prioritized += 1;
+            }
+            let entry = Entry {
+                data: sel.downgrade(),
+                priority: prio.unwrap_or(0),
+                key: key.unwrap_or(id as u64),
+                refs: 1,
+            };
+            inner.wait_list.insert(id, entry);
+        }
+
+        // Careful not to deadlock in `register`.
+        sel.register(self.as_payload());
+
         if sel.ready() {
-            inner.ready_list.insert(id);
+            let mut inner = self.inner.lock().unwrap();
+            if inner.wait_list.contains_key(&id) {
+                inner.ready_list.insert(id);
+            }
         }
 
-        inner.wait_list.insert(id, Entry { data: sel.downgrade() });
+        true
+    }
+
+    /// Adds a target to the select object and returns a guard that removes the target
+    /// again when it's dropped.
+    ///
+    /// Dropping the guard undoes this call like `remove`. If the target had already been
+    /// added before this call, it stays in the `Select` object.
+    pub fn add_scoped<'s, T: Selectable<'a>+'a>(&'s self,
+                                               sel: &'s T) -> SelectGuard<'s, 'a, T> {
+        self.add(sel);
+        SelectGuard { select: self, target: sel }
     }
 
     /// Removes a target from the `Select` object. Returns `true` if the target was
     /// previously registered in the `Select` object, `false` otherwise.
+    ///
+    /// If the target has been added more than once, only the reference count is
+    /// decreased and the target stays in the `Select` object.
     pub fn remove<T: Selectable<'a>>(&self, sel: &T) -> bool {
         let sel = sel.as_selectable();
 
         let mut inner = self.inner.lock().unwrap();
 
-        if inner.wait_list.remove(&sel.unique_id()).is_none() {
-            return false;
+        match inner.wait_list.get_mut(&sel.unique_id()) {
+            Some(ref mut e) if e.refs > 1 => {
+                e.refs -= 1;
+                return true;
+            },
+            Some(_) => { },
+            None => return false,
         }
+        inner.remove_entry(sel.unique_id());
         inner.ready_list.remove(&sel.unique_id());
 
         // Careful not to deadlock in `unregister`.
@@ -67,45 +218,268 @@ impl<'a> Select<'a> {
         true
     }
 
+    /// Like `wait` but afterwards removes all returned targets whose peers have
+    /// disconnected from the `Select` object. The ids of the removed targets are still
+    /// returned so that the caller can handle the disconnect.
+    pub fn wait_auto_prune<'b>(&self, ready: &'b mut [usize]) -> &'b mut [usize] {
+        let ready = self.wait(ready);
+        self.prune(ready);
+        ready
+    }
+
+    fn prune(&self, ids: &[usize]) {
+        let mut disconnected = vec!();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            for &id in ids {
+                let target = match inner.wait_list.get(&id) {
+                    Some(e) => match e.data.upgrade() {
+                        Some(t) => t,
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                if target.is_disconnected() {
+                    inner.remove_entry(id);
+                    inner.ready_list.remove(&id);
+                    disconnected.push(target);
+                }
+            }
+        }
+
+        // Careful not to deadlock in `unregister`.
+        for target in disconnected {
+            target.unregister(self.inner.unique_id());
+        }
+    }
+
+    /// Returns an iterator that waits for the `consumers` and yields the received
+    /// messages together with the ids of the consumers they were received from.
+    ///
+    /// `consumers` maps the ids of the consumers to the consumers themselves and the
+    /// consumers must have been added to the `Select` object. Ready consumers that turn
+    /// out to be empty because someone else received the message first are skipped.
+    /// Consumers that have disconnected are removed from the `Select` object. The
+    /// iterator ends once all consumers have disconnected.
+    ///
+    /// The `Select` object should not contain other targets. Ready targets that are not
+    /// in `consumers` are ignored and make the iterator spin.
+    pub fn iter_with<'s, T, R>(&'s self, consumers: &'s HashMap<usize, R>)
+                               -> SelectIter<'s, 'a, T, R>
+        where R: RecvSelectable<'a, T>+'a,
+    {
+        SelectIter {
+            select: self,
+            consumers: consumers,
+            buf: vec!(0; cmp::max(consumers.len(), 1)),
+            pending: vec!(),
+            disconnected: HashSet::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the ids of all targets currently registered in the `Select` object. The
+    /// ids are returned in no particular order.
+    pub fn target_ids(&self) -> Vec<usize> {
+        let inner = self.inner.lock().unwrap();
+        inner.wait_list.keys().cloned().collect()
+    }
+
+    /// Returns the number of targets currently registered in the `Select` object.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().wait_list.len()
+    }
+
+    /// Returns whether no targets are registered in the `Select` object.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the combined capacity of the ready lists.
+    #[cfg(test)]
+    pub fn ready_capacity(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner.ready_list.capacity() + inner.ready_list2.capacity()
+    }
+
+    /// Returns the number of targets with a non-zero priority.
+    #[cfg(test)]
+    pub fn prioritized_count(&self) -> usize {
+        self.inner.lock().unwrap().prioritized
+    }
+
     /// Waits for any of the targets in the `Select` object to become ready. The ids of
     /// the ready targets will be stored in `ready`. Returns the prefix containing the set
     /// of stored `ids`.
     ///
     /// If the select object is empty, an empty slice is returned immediately.
     pub fn wait<'b>(&self, ready: &'b mut [usize]) -> &'b mut [usize] {
+        let (_, n) = self.wait_locked(self.inner.lock().unwrap(), ready);
+        &mut ready[..n]
+    }
+
+    /// Like `wait` but returns the ids of all ready targets.
+    ///
+    /// This allocates a new vector on every call. Use `wait` with a reused buffer in hot
+    /// loops.
+    pub fn wait_vec(&self) -> Vec<usize> {
         let mut inner = self.inner.lock().unwrap();
 
         if inner.wait_list.is_empty() {
-            return &mut [];
+            return vec!();
+        }
+
+        inner.check_ready_list(&mut []);
+        while inner.ready_list.len() == 0 {
+            inner = self.condvar.wait(inner).unwrap();
+        }
+
+        let mut ready = vec!(0; inner.ready_list.len());
+        inner.copy_ready(&mut ready);
+        ready
+    }
+
+    /// Like `wait` but stores the keys of the ready targets instead of their ids. See
+    /// `add_keyed`.
+    ///
+    /// Targets that have been removed from the `Select` object because they went away,
+    /// e.g., a dropped channel endpoint or nested `Select` object, no longer have a key
+    /// and are reported as `None`.
+    pub fn wait_keyed<'b>(&self, keys: &'b mut [Option<u64>]) -> &'b mut [Option<u64>] {
+        let mut inner = self.inner.lock().unwrap();
+        // If another thread is waiting at the same time, it has taken the buffer and we
+        // allocate a new one.
+        let mut ids = mem::replace(&mut inner.key_ids, vec!());
+        ids.clear();
+        ids.extend(iter::repeat(0).take(keys.len()));
+        let (mut inner, n) = self.wait_locked(inner, &mut ids);
+        // We still hold the lock so the keys are those of the reported targets.
+        for i in 0..n {
+            keys[i] = inner.wait_list.get(&ids[i]).map(|e| e.key);
+        }
+        inner.key_ids = ids;
+        &mut keys[..n]
+    }
+
+    /// Implements `wait`. Returns the lock and the number of stored ids.
+    fn wait_locked<'i>(&self, mut inner: MutexGuard<'i, Inner<'a>>,
+                       ready: &mut [usize]) -> (MutexGuard<'i, Inner<'a>>, usize) {
+        if inner.wait_list.is_empty() {
+            return (inner, 0);
         }
 
         if let Some(n) = inner.check_ready_list(ready) {
-            return &mut ready[..n];
+            return (inner, n);
         }
 
         while inner.ready_list.len() == 0 {
             inner = self.condvar.wait(inner).unwrap();
         }
 
-        let min = cmp::min(ready.len(), inner.ready_list.len());
+        let n = inner.copy_ready(ready);
+        (inner, n)
+    }
+
+    /// Waits until every target in the `Select` object has been ready at least once since
+    /// this function was called. The ids of all targets will be stored in `ready` in
+    /// ascending order. Returns the prefix containing the set of stored ids.
+    ///
+    /// A target doesn't have to stay ready until the function returns. Targets that are
+    /// removed while this function is waiting don't have to become ready.
+    ///
+    /// If the select object is empty, an empty slice is returned immediately.
+    pub fn wait_all<'b>(&self, ready: &'b mut [usize]) -> &'b mut [usize] {
+        let mut seen = HashSet::new();
+        let mut inner = self.inner.lock().unwrap();
+
+        // Forget the targets that were ready before this call but aren't anymore.
+        inner.check_ready_list(&mut []);
+
+        loop {
+            // A target in the ready list has become ready since we last looked even if
+            // it has been drained again in the meantime.
+            seen.extend(inner.ready_list.iter().cloned());
+            if inner.wait_list.keys().all(|id| seen.contains(id)) {
+                break;
+            }
+            // Remove the targets that are no longer ready so that they don't hide the
+            // targets we're still waiting for.
+            inner.check_ready_list(&mut []);
+            inner = self.condvar.wait(inner).unwrap();
+        }
+
+        let mut ids: Vec<_> = inner.wait_list.keys().cloned().collect();
+        ids.sort();
+        let min = cmp::min(ready.len(), ids.len());
         for i in 0..min {
-            ready[i] = inner.ready_list[i];
+            ready[i] = ids[i];
         }
         &mut ready[..min]
     }
 
+    /// Like `wait` but also reports whether each of the returned targets is ready because
+    /// its peer has disconnected.
+    pub fn wait_with_status<'b>(&self, ready: &'b mut [(usize, Status)])
+                                -> &'b mut [(usize, Status)] {
+        let mut ids = vec!(0; ready.len());
+        let n = self.wait(&mut ids).len();
+
+        let mut targets = vec!();
+        {
+            let inner = self.inner.lock().unwrap();
+            for (i, &id) in ids[..n].iter().enumerate() {
+                let target = inner.wait_list.get(&id).and_then(|e| e.data.upgrade());
+                let status = match target {
+                    Some(ref t) if t.is_disconnected() => Status::Disconnected,
+                    _ => Status::Ready,
+                };
+                ready[i] = (id, status);
+                targets.push(target);
+            }
+        }
+
+        // Careful not to deadlock if we hold the last reference to a target.
+        drop(targets);
+
+        &mut ready[..n]
+    }
+
+    /// Checks which targets are ready without blocking. The ids of the ready targets are
+    /// stored in `ready` as in `wait`.
+    pub fn poll<'b>(&self, ready: &'b mut [usize]) -> PollResult<'b> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.wait_list.is_empty() {
+            return PollResult::Empty;
+        }
+
+        match inner.check_ready_list(ready) {
+            Some(n) => PollResult::Ready(&mut ready[..n]),
+            _ => PollResult::WouldBlock,
+        }
+    }
+
     /// Waits for any of the targets in the `Select` object to become ready. The semantics
     /// are as for the `wait` function except that
     ///
     /// - if `duration` is some duration, then it will sleep for at most `duration`, and
     /// - if `duration` is none, then it will only check if at the time of the call any
-    ///   targets are ready and return immediately.
+    ///   targets are ready and return immediately. `poll` does the same but makes it
+    ///   possible to tell an empty `Select` object apart from one without ready targets.
     ///
     /// # Return value
     ///
     /// Returns `None` if the timeout expired.
     pub fn wait_timeout<'b>(&self, ready: &'b mut [usize],
                             duration: Option<Duration>) -> Option<&'b mut [usize]> {
+        let duration = match duration {
+            Some(d) => d,
+            _ => return match self.poll(ready) {
+                PollResult::Ready(ready) => Some(ready),
+                PollResult::WouldBlock | PollResult::Empty => Some(&mut []),
+            },
+        };
+
         let mut inner = self.inner.lock().unwrap();
 
         if inner.wait_list.is_empty() {
@@ -116,49 +490,259 @@ impl<'a> Select<'a> {
             return Some(&mut ready[..n]);
         }
 
-        let duration = match duration {
-            Some(d) => d,
-            _ => return Some(&mut []),
-        };
-
-        let (inner, notified) = self.condvar.wait_timeout_with(inner, duration, |inner| {
-            inner.unwrap().ready_list.len() > 0
+        let (mut inner, result) = self.condvar.wait_timeout_with(inner, duration, |i| {
+            i.unwrap().ready_list.len() > 0
         }).unwrap();
 
-        if !notified.timed_out() {
+        if result.timed_out() {
             return None;
         }
 
-        let min = cmp::min(ready.len(), inner.ready_list.len());
-        for i in 0..min {
-            ready[i] = inner.ready_list[i];
-        }
-        Some(&mut ready[..min])
+        let n = inner.copy_ready(ready);
+        Some(&mut ready[..n])
     }
 }
 
 unsafe impl<'a> Sync for Select<'a> { }
 unsafe impl<'a> Send for Select<'a> { }
 
+impl<'a> Drop for Select<'a> {
+    fn drop(&mut self) {
+        // Make the wait queues take the slow path once more so that they notice that
+        // we're gone and remove us.
+        self.generation.fetch_add(1, SeqCst);
+    }
+}
+
+/// A guard that removes a target from a `Select` object when it's dropped.
+///
+/// See `Select::add_scoped`.
+pub struct SelectGuard<'s, 'a: 's, T: Selectable<'a>+'a> {
+    select: &'s Select<'a>,
+    target: &'s T,
+}
+
+impl<'s, 'a: 's, T: Selectable<'a>+'a> SelectGuard<'s, 'a, T> {
+    /// Returns the id of the target.
+    pub fn id(&self) -> usize {
+        self.target.id()
+    }
+}
+
+impl<'s, 'a: 's, T: Selectable<'a>+'a> Drop for SelectGuard<'s, 'a, T> {
+    fn drop(&mut self) {
+        self.select.remove(self.target);
+    }
+}
+
+/// An iterator over the messages received from the consumers in a `Select` object.
+///
+/// See `Select::iter_with`.
+pub struct SelectIter<'s, 'a: 's, T, R: RecvSelectable<'a, T>+'a> {
+    select: &'s Select<'a>,
+    consumers: &'s HashMap<usize, R>,
+    // Buffer passed to `wait`.
+    buf: Vec<usize>,
+    // Ready ids returned by the last `wait` that have not been handled yet, in reverse
+    // order.
+    pending: Vec<usize>,
+    // The ids of the consumers that have disconnected.
+    disconnected: HashSet<usize>,
+    _marker: PhantomData<T>,
+}
+
+impl<'s, 'a: 's, T, R: RecvSelectable<'a, T>+'a> Iterator for SelectIter<'s, 'a, T, R> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<(usize, T)> {
+        loop {
+            if self.disconnected.len() == self.consumers.len() {
+                return None;
+            }
+            let id = match self.pending.pop() {
+                Some(id) => id,
+                None => {
+                    let n = self.select.wait(&mut self.buf).len();
+                    if n == 0 {
+                        // The `Select` object is empty.
+                        return None;
+                    }
+                    self.pending.extend(self.buf[..n].iter().rev().cloned());
+                    continue;
+                },
+            };
+            let consumer = match self.consumers.get(&id) {
+                Some(c) => c,
+                _ => continue,
+            };
+            match consumer.recv_async() {
+                Ok(val) => return Some((id, val)),
+                Err(Error::Disconnected) => {
+                    // It would be ready forever.
+                    self.select.remove(consumer);
+                    self.disconnected.insert(id);
+                },
+                Err(_) => { },
+            }
+        }
+    }
+}
+
+/// Hasher for the ids in `wait_list`.
+///
+/// The ids are the addresses of distinct objects, so there is no need for a DoS resistant
+/// hash function. We only have to move the entropy into the lower bits because the
+/// addresses are aligned.
+#[derive(Default)]
+struct IdHasher {
+    hash: u64,
+}
+
+impl Hasher for IdHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(b as u64);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        let h = (self.hash ^ n).wrapping_mul(0x9e3779b97f4a7c15);
+        self.hash = h ^ (h >> 32);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+}
+
+type IdMap<V> = HashMap<usize, V, BuildHasherDefault<IdHasher>>;
+
+/// The spare capacity of the ready lists that is always retained.
+const MIN_READY_CAPACITY: usize = 64;
+
 struct Inner<'a> {
-    wait_list: HashMap<usize, Entry<'a>>,
+    wait_list: IdMap<Entry<'a>>,
 
     ready_list: SortedVec<usize>,
     ready_list2: SortedVec<usize>,
 
     condvar: Arc<Condvar>,
+
+    // Incremented every time targets might be removed from `ready_list`. A `WaitQueue`
+    // that has added its target to `ready_list` doesn't have to do so again until this
+    // changes.
+    generation: Arc<AtomicUsize>,
+
+    // The `Select` objects this `Select` object has been added to.
+    parents: WaitQueue<'a>,
+
+    policy: Policy,
+    // Where the next `wait` continues. For `Lowest` this is the last id that was
+    // reported, for `RoundRobin` the first.
+    cursor: usize,
+    // The state of the xorshift generator used by `Random`.
+    rng: u64,
+    // The number of targets with a non-zero priority.
+    prioritized: usize,
+    // Scratch space for the ids in `wait_keyed`.
+    key_ids: Vec<usize>,
 }
 
 impl<'a> Inner<'a> {
-    fn new(condvar: Arc<Condvar>) -> Inner<'a> {
+    fn new(condvar: Arc<Condvar>, generation: Arc<AtomicUsize>,
+           policy: Policy) -> Inner<'a> {
+        let rng = match policy {
+            // xorshift gets stuck at 0.
+            Policy::Random { seed } if seed != 0 => seed,
+            _ => 0x2545f4914f6cdd1d,
+        };
         Inner {
-            wait_list: HashMap::new(),
+            wait_list: IdMap::default(),
             ready_list: SortedVec::new(),
             ready_list2: SortedVec::new(),
-            condvar: condvar
+            condvar: condvar,
+            generation: generation,
+            parents: WaitQueue::new(),
+            policy: policy,
+            cursor: 0,
+            rng: rng,
+            prioritized: 0,
+            key_ids: vec!(),
         }
     }
 
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    /// Copies a prefix of the ready list, rotated according to the policy, into `ready`.
+    /// Returns the number of copied ids.
+    fn copy_ready(&mut self, ready: &mut [usize]) -> usize {
+        let len = self.ready_list.len();
+        let n = cmp::min(ready.len(), len);
+        if n == 0 {
+            return 0;
+        }
+        let after_cursor = match self.ready_list.binary_search(&self.cursor) {
+            Ok(pos) => (pos + 1) % len,
+            Err(pos) => pos % len,
+        };
+        let start = match self.policy {
+            Policy::Lowest if n == len => 0,
+            Policy::Lowest | Policy::RoundRobin => after_cursor,
+            Policy::Random { .. } => (self.next_random() % len as u64) as usize,
+        };
+        if self.prioritized > 0 {
+            return self.copy_ready_prioritized(ready, start, n);
+        }
+        for i in 0..n {
+            ready[i] = self.ready_list[(start + i) % len];
+        }
+        match self.policy {
+            Policy::Lowest => {
+                self.cursor = ready[n - 1];
+                // The window might have wrapped around.
+                ready[..n].sort();
+            },
+            _ => self.cursor = ready[0],
+        }
+        n
+    }
+
+    /// Like `copy_ready` but targets with a higher priority come first. Targets with the
+    /// same priority keep the order of the policy.
+    fn copy_ready_prioritized(&mut self, ready: &mut [usize], start: usize,
+                              n: usize) -> usize {
+        let len = self.ready_list.len();
+        let mut order: Vec<(u32, usize)> = (0..len).map(|i| {
+            let id = self.ready_list[(start + i) % len];
+            (self.wait_list.get(&id).map(|e| e.priority).unwrap_or(0), id)
+        }).collect();
+        // `sort_by` is stable.
+        order.sort_by(|a, b| b.0.cmp(&a.0));
+        if self.policy == Policy::Lowest {
+            self.cursor = order[n - 1].1;
+            // The window might have wrapped around.
+            order[..n].sort_by(|a, b| match b.0.cmp(&a.0) {
+                Ordering::Equal => a.1.cmp(&b.1),
+                o => o,
+            });
+        } else {
+            self.cursor = order[0].1;
+        }
+        for i in 0..n {
+            ready[i] = order[i].1;
+        }
+        n
+    }
+
     fn add_ready(&mut self, id: usize) -> bool {
         if !self.wait_list.contains_key(&id) {
             return false;
@@ -166,22 +750,51 @@ impl<'a> Inner<'a> {
 
         self.ready_list.insert(id);
         self.condvar.notify_one();
+        self.parents.notify();
 
         true
     }
 
+    /// Removes a target from the wait list and keeps `prioritized` up to date.
+    fn remove_entry(&mut self, id: usize) -> Option<Entry<'a>> {
+        let entry = self.wait_list.remove(&id);
+        if let Some(ref e) = entry {
+            if e.priority > 0 {
+                self.prioritized -= 1;
+            }
+        }
+        entry
+    }
+
     fn going_away(&mut self, id: usize) -> bool {
-        if self.wait_list.remove(&id).is_none() {
+        if self.remove_entry(id).is_none() {
             return false;
         }
 
         self.ready_list.insert(id);
         self.condvar.notify_one();
+        self.parents.notify();
 
         true
     }
 
+    fn any_ready(&self) -> bool {
+        // A parent that finds us not ready drops us from its ready list. It has to be
+        // notified again when one of our targets becomes ready. See `check_ready_list`.
+        self.generation.fetch_add(1, SeqCst);
+
+        self.ready_list.iter().any(|id| {
+            self.wait_list.get(id).and_then(|e| e.data.upgrade())
+                                  .map(|e| e.ready()).unwrap_or(false)
+        })
+    }
+
     fn check_ready_list(&mut self, ready: &mut [usize]) -> Option<usize> {
+        // This has to happen before we call `ready` on the targets. Otherwise a target
+        // that becomes ready while we're checking it might skip the notification because
+        // it's still in the old `ready_list`.
+        self.generation.fetch_add(1, SeqCst);
+
         let all = 0..self.ready_list.len();
         for id in self.ready_list.drain(all) {
             if let Some(target) = self.wait_list.get(&id) {
@@ -192,23 +805,61 @@ impl<'a> Inner<'a> {
         }
         mem::swap(&mut self.ready_list, &mut self.ready_list2);
 
-        match cmp::min(ready.len(), self.ready_list.len()) {
+        // `ready_list2` is empty now but keeps the capacity of the old list. Don't let a
+        // single burst of ready targets inflate it for the lifetime of the object.
+        let len = self.ready_list.len();
+        if self.ready_list2.capacity() > cmp::max(MIN_READY_CAPACITY, 4 * len) {
+            *self.ready_list2 = Vec::with_capacity(len);
+        }
+
+        match self.copy_ready(ready) {
             0 => None,
-            n => {
-                for i in 0..n {
-                    ready[i] = self.ready_list[i];
-                }
-                Some(n)
-            }
+            n => Some(n),
         }
     }
 }
 
 unsafe impl<'a> Send for Inner<'a> { }
 
+impl<'a> Selectable<'a> for Select<'a> {
+    fn id(&self) -> usize {
+        self.inner.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.inner.as_trait(&*self.inner as &(_Selectable+'a)) }
+    }
+}
+
+// A `Select` object holds its own lock while it notifies its parents and the parents
+// hold their locks while they call `ready`. Therefore `ready` must not block on the lock
+// and conservatively reports readiness if someone else is holding it.
+unsafe impl<'a> _Selectable<'a> for Mutex<Inner<'a>> {
+    fn ready(&self) -> bool {
+        match self.try_lock() {
+            Ok(inner) => inner.any_ready(),
+            Err(TryLockError::WouldBlock) => true,
+            Err(TryLockError::Poisoned(_)) => true,
+        }
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        self.lock().unwrap().parents.add(load);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.lock().unwrap().parents.remove(id);
+    }
+}
+
 #[derive(Clone)]
 struct Entry<'a> {
     data: WeakTrait<_Selectable<'a>+'a>,
+    priority: u32,
+    // The key reported by `wait_keyed`.
+    key: u64,
+    // How often the target has been added.
+    refs: usize,
 }
 
 impl<'a> PartialEq for Entry<'a> {
@@ -240,10 +891,19 @@ impl<'a> Hash for Entry<'a> {
 /// A structure stored by `Selectable` objects to interact with `Select` objects that want
 /// to be notified when the `Selectable` object becomes ready.
 pub struct WaitQueue<'a> {
-    queue: Vec<Weak<Mutex<Inner<'a>>>>,
+    queue: Vec<Waiter<'a>>,
     id: usize,
 }
 
+struct Waiter<'a> {
+    select: Weak<Mutex<Inner<'a>>>,
+    generation: Arc<AtomicUsize>,
+    condvar: Arc<Condvar>,
+    // The generation of the `Select` object when we last added our target to its ready
+    // list.
+    notified: usize,
+}
+
 impl<'a> WaitQueue<'a> {
     /// Creates a new `WaitQueue`. This function does not allocate.
     pub fn new() -> WaitQueue<'a> {
@@ -253,6 +913,22 @@ impl<'a> WaitQueue<'a> {
         }
     }
 
+    /// Creates a new `WaitQueue` that can hold `n` `Select` objects without
+    /// reallocating.
+    pub fn with_capacity(n: usize) -> WaitQueue<'a> {
+        WaitQueue {
+            queue: Vec::with_capacity(n),
+            id: 0,
+        }
+    }
+
+    /// Returns the number of `Select` objects the `WaitQueue` can hold without
+    /// reallocating.
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+
     /// Sets the `id` of the `Selectable` object containing the `WaitQueue`. This id must
     /// be the id returned by `Selectable::as_selectable().unique_id()`. This function
     /// must be called with the correct id before any other functions are called.
@@ -263,14 +939,19 @@ impl<'a> WaitQueue<'a> {
     /// Add a `Select` object to the `WaitQueue`. Returns the number of `Select` objects
     /// contained in the `WaitQueue` after this call.
     pub fn add(&mut self, load: Payload<'a>) -> usize {
-        self.queue.push(load.data);
+        self.queue.push(Waiter {
+            select: load.data,
+            generation: load.generation,
+            condvar: load.condvar,
+            notified: !0,
+        });
         self.queue.len()
     }
 
     /// Removes a `Select` object from the `WaitQueue`. Returns the number of `Select`
     /// objects contained in the `WaitQueue` after this call.
     pub fn remove(&mut self, id: usize) -> usize {
-        if let Some(p) = self.queue.iter().position(|el| el.unique_id() == id) {
+        if let Some(p) = self.queue.iter().position(|el| el.select.unique_id() == id) {
             self.queue.remove(p);
         }
         self.queue.len()
@@ -280,10 +961,19 @@ impl<'a> WaitQueue<'a> {
     /// object has become ready. Returns the number of `Select` objects contained in the
     /// `WaitQueue` after this call. This function might remove `Select` objects from the
     /// `WaitQueue`.
+    ///
+    /// `Select` objects that still have the target in their ready list from a previous
+    /// call are not locked. Only their sleeping threads are woken up.
     pub fn notify(&mut self) -> usize {
         let mut i = 0;
         while i < self.queue.len() {
-            let strong = match self.queue[i].upgrade() {
+            let generation = self.queue[i].generation.load(SeqCst);
+            if generation == self.queue[i].notified {
+                self.queue[i].condvar.notify_one();
+                i += 1;
+                continue;
+            }
+            let strong = match self.queue[i].select.upgrade() {
                 Some(s) => s,
                 _ => {
                     self.queue.swap_remove(i);
@@ -291,7 +981,11 @@ impl<'a> WaitQueue<'a> {
                 },
             };
             let mut select = strong.lock().unwrap();
-            select.add_ready(self.id);
+            if select.add_ready(self.id) {
+                // If the generation has changed in the meantime, the next call takes the
+                // slow path again.
+                self.queue[i].notified = generation;
+            }
             i += 1;
         }
         self.queue.len()
@@ -303,7 +997,7 @@ impl<'a> WaitQueue<'a> {
     pub fn clear(&mut self) {
         let all = 0..self.queue.len();
         for el in self.queue.drain(all) {
-            if let Some(strong) = el.upgrade() {
+            if let Some(strong) = el.select.upgrade() {
                 let mut select = strong.lock().unwrap();
                 select.going_away(self.id);
             }
@@ -320,4 +1014,6 @@ impl<'a> Drop for WaitQueue<'a> {
 /// Container passed from the `Select` object to a `WaitQueue`.
 pub struct Payload<'a> {
     data: Weak<Mutex<Inner<'a>>>,
+    generation: Arc<AtomicUsize>,
+    condvar: Arc<Condvar>,
 }
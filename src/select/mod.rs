@@ -34,6 +34,23 @@
 //! when a target becomes ready. The others will continue to sleep until another target
 //! becomes ready.
 //!
+//! A `Select` object is itself `Selectable` and can be added to another `Select` object.
+//! The parent considers the child ready if any of the child's targets is ready. The
+//! parent's `wait` only reports the id of the child, so one has to call `wait` on the
+//! child to find out which of its targets is ready. The readiness reported by a child
+//! can be spurious. Adding a `Select` object to itself, directly or indirectly, leads to
+//! a deadlock.
+//!
+//! The producers of bounded SPSC and MPSC channels are `Selectable` as well. They are
+//! ready when a message can be sent without blocking, so a single `Select` object can
+//! wait for readable and writable channels at the same time.
+//!
+//! Channels of the standard library can be added via the `StdReceiver` wrapper.
+//!
+//! If more targets are ready than fit into the buffer passed to `wait`, successive calls
+//! report successive windows of the ready targets so that every ready target is
+//! eventually reported. See `Policy` for other orders.
+//!
 //! `wait` will return an increasing number of unique ids that should be compared to the
 //! return values of the `id` functions of `Selectable` objects. Therefore, all ready
 //! targets can be found in `O(number_of_targets)` or
@@ -62,13 +79,66 @@
 //! To keep the API simple, this module also provides a `WaitQueue` structure which the
 //! targets have to store to interact with `Select` objects.
 
-pub use self::imp::{Select, WaitQueue, Payload};
+pub use self::imp::{Select, SelectGuard, SelectIter, WaitQueue, Payload, Status, Policy,
+                    PollResult};
+pub use self::adapter::{StdReceiver};
 
 use arc::{ArcTrait};
-use {Sendable};
+use {Error, Sendable};
+
+/// Waits until one of several consumers is ready, receives a message from it, and runs
+/// the corresponding arm.
+///
+/// Each arm has the form `pattern = consumer => expression`, where `consumer` is an
+/// identifier bound to a `RecvSelectable` object. The consumers can have different
+/// message types. The result of `recv_async` is bound to the pattern of the arm whose
+/// consumer is ready, as in a `let` statement, and the whole macro evaluates to the
+/// arm's expression. Like in `recv_any`, an `Empty` result is never passed to an arm.
+///
+/// The macro creates a temporary `Select` object, so the usual costs of creating a
+/// `Select` object and adding targets apply every time it's evaluated.
+///
+/// The arms run inside a loop of the macro. Unlabeled `break` and `continue` in an arm
+/// refer to that loop. Use labels to affect enclosing loops.
+///
+/// ### Example
+///
+/// ```ignore
+/// let msg = select! {
+///     n = numbers => format!("number: {:?}", n),
+///     s = strings => format!("string: {:?}", s),
+/// };
+/// ```
+#[macro_export]
+macro_rules! select {
+    ($($name:pat = $recv:ident => $code:expr),+ $(,)*) => {{
+        let select = $crate::select::Select::new();
+        $( select.add(&$recv); )+
+        let mut buf = [0];
+        let ret;
+        loop {
+            let id = select.wait(&mut buf)[0];
+            $(
+                if id == $crate::select::Selectable::id(&$recv) {
+                    match $crate::select::RecvSelectable::recv_async(&$recv) {
+                        Err($crate::Error::Empty) => continue,
+                        rv => {
+                            let $name = rv;
+                            ret = $code;
+                            break;
+                        },
+                    }
+                }
+            )+
+        }
+        ret
+    }};
+}
 
 mod imp;
-//#[cfg(test)] mod test;
+mod adapter;
+#[cfg(test)] mod test;
+#[cfg(test)] mod bench;
 
 // Traits are here because https://github.com/rust-lang/rust/issues/16264
 
@@ -80,6 +150,49 @@ pub trait Selectable<'a> {
     fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a>;
 }
 
+/// A `Selectable` object that messages can be received from without blocking.
+///
+/// This trait is implemented by all consumers so that they can be used with `recv_any`.
+pub trait RecvSelectable<'a, T>: Selectable<'a> {
+    /// Receives a message without blocking. See the `recv_async` method of the
+    /// implementing type.
+    fn recv_async(&self) -> Result<T, Error>;
+}
+
+/// Waits until one of `consumers` is ready and receives a message from it.
+///
+/// Returns the index of the consumer in `consumers` and the result of its `recv_async`
+/// call. An `Empty` result is never returned. If a ready consumer turns out to be empty
+/// because someone else received the message first, the next ready consumer is tried.
+///
+/// If several consumers are ready at the same time, the one with the smallest `id` is
+/// preferred.
+///
+/// ### Panic
+///
+/// Panics if `consumers` is empty.
+pub fn recv_any<'a, T, R>(consumers: &[&R]) -> (usize, Result<T, Error>)
+    where R: RecvSelectable<'a, T>+'a,
+{
+    assert!(consumers.len() > 0, "recv_any called without consumers");
+
+    let select = Select::new();
+    for &consumer in consumers {
+        select.add(consumer);
+    }
+
+    let mut ids = vec!(0; consumers.len());
+    loop {
+        for &id in select.wait(&mut ids).iter() {
+            let idx = consumers.iter().position(|c| c.id() == id).unwrap();
+            match consumers[idx].recv_async() {
+                Err(Error::Empty) => { },
+                rv => return (idx, rv),
+            }
+        }
+    }
+}
+
 /// The object that will be stored in a `Select` structure while the `Selectable` object
 /// is registered.
 ///
@@ -91,6 +204,12 @@ pub unsafe trait _Selectable<'a>: Sync+Sendable {
     /// This function must not try to acquire any locks that are also held while the
     /// implementation interacts with the `WaitQueue` object.
     fn ready(&self) -> bool;
+    /// Returns `true` if the object is ready only because its peer has disconnected and
+    /// it will never produce anything again. The default implementation returns
+    /// `false`.
+    ///
+    /// The same restrictions as for `ready` apply.
+    fn is_disconnected(&self) -> bool { false }
     /// Registers a `Select` object with the `Selectable` object. The payload must be
     /// passed to the `WaitQueue`.
     fn register(&self, Payload<'a>);
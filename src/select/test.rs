@@ -1,10 +1,15 @@
+use std::collections::{HashMap};
 use std::thread::{self, sleep_ms};
-use std::sync::{Arc};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicUsize};
 use std::sync::atomic::Ordering::{SeqCst};
+use std::time::{Duration};
 
+use arc::{self, ArcTrait};
 use spsc::unbounded::{new};
-use super::{Select, Selectable};
+use {Error};
+use super::{Select, Selectable, _Selectable, Payload, Status, Policy, StdReceiver,
+            PollResult, WaitQueue, recv_any};
 
 fn ms_sleep(ms: i64) {
     sleep_ms(ms as u32);
@@ -131,3 +136,666 @@ fn select_chance() {
     ms_sleep(100);
     assert_eq!(counter1.swap(0, SeqCst), 1);
 }
+
+#[test]
+fn target_ids() {
+    let (_send, recv) = new::<u8>();
+    let (_send2, recv2) = new::<u8>();
+    let select = Select::new();
+    assert!(select.target_ids().is_empty());
+    select.add(&recv);
+    select.add(&recv2);
+    let mut ids = select.target_ids();
+    ids.sort();
+    let mut expected = vec!(recv.id(), recv2.id());
+    expected.sort();
+    assert_eq!(ids, expected);
+    select.remove(&recv);
+    assert_eq!(select.target_ids(), vec!(recv2.id()));
+}
+
+// A target that counts how often it's registered.
+struct Counter {
+    registered: AtomicUsize,
+}
+
+unsafe impl<'a> _Selectable<'a> for Counter {
+    fn ready(&self) -> bool {
+        false
+    }
+
+    fn register(&self, _: Payload<'a>) {
+        self.registered.fetch_add(1, SeqCst);
+    }
+
+    fn unregister(&self, _: usize) {
+        self.registered.fetch_sub(1, SeqCst);
+    }
+}
+
+struct CounterHandle(arc::Arc<Counter>);
+
+impl<'a> Selectable<'a> for CounterHandle {
+    fn id(&self) -> usize {
+        self.0.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.0.as_trait(&*self.0 as &(_Selectable+'a)) }
+    }
+}
+
+#[test]
+fn try_add() {
+    let counter = Counter { registered: AtomicUsize::new(0) };
+    let target = CounterHandle(arc::Arc::new(counter));
+    let select = Select::new();
+    assert!(select.try_add(&target));
+    assert!(!select.try_add(&target));
+    select.add(&target);
+    assert_eq!(select.len(), 1);
+    assert_eq!(target.0.registered.load(SeqCst), 1);
+    // The target was added three times.
+    assert!(select.remove(&target));
+    assert!(select.remove(&target));
+    assert_eq!(target.0.registered.load(SeqCst), 1);
+    assert!(select.remove(&target));
+    assert_eq!(target.0.registered.load(SeqCst), 0);
+    assert!(!select.remove(&target));
+}
+
+#[test]
+fn len() {
+    let (_send, recv) = new::<u8>();
+    let (_send2, recv2) = new::<u8>();
+    let select = Select::new();
+    assert_eq!(select.len(), 0);
+    assert!(select.is_empty());
+    select.add(&recv);
+    select.add(&recv2);
+    assert_eq!(select.len(), 2);
+    assert!(!select.is_empty());
+    select.remove(&recv);
+    select.remove(&recv2);
+    assert!(select.is_empty());
+}
+
+#[test]
+fn add_scoped() {
+    let (_send, recv) = new::<u8>();
+    let (_send2, recv2) = new::<u8>();
+    let select = Select::new();
+    select.add(&recv);
+    {
+        let guard = select.add_scoped(&recv2);
+        assert_eq!(guard.id(), recv2.id());
+        assert_eq!(select.len(), 2);
+    }
+    assert_eq!(select.len(), 1);
+    assert_eq!(select.target_ids(), vec!(recv.id()));
+}
+
+#[test]
+fn nested_no_wait() {
+    let (send, recv) = new();
+    let (_send2, recv2) = new::<u8>();
+    send.send(1u8).unwrap();
+    let child = Select::new();
+    child.add(&recv);
+    let child2 = Select::new();
+    child2.add(&recv2);
+    let parent = Select::new();
+    parent.add(&child);
+    parent.add(&child2);
+    assert_eq!(parent.wait(&mut [0, 0]), &mut [child.id()][..]);
+    assert_eq!(child.wait(&mut [0]), &mut [recv.id()][..]);
+}
+
+#[test]
+fn nested_wait() {
+    let (send, recv) = new();
+    let (_send2, recv2) = new::<u8>();
+    let child = Select::new();
+    child.add(&recv);
+    let child2 = Select::new();
+    child2.add(&recv2);
+    let parent = Select::new();
+    parent.add(&child);
+    parent.add(&child2);
+    assert_eq!(parent.wait_timeout(&mut [0, 0], None), Some(&mut [][..]));
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send(1u8).unwrap();
+    });
+    assert_eq!(parent.wait(&mut [0, 0]), &mut [child.id()][..]);
+    assert_eq!(child.wait(&mut [0]), &mut [recv.id()][..]);
+}
+
+#[test]
+fn nested_remove() {
+    let (send, recv) = new();
+    let child = Select::new();
+    child.add(&recv);
+    let parent = Select::new();
+    parent.add(&child);
+    assert!(parent.remove(&child));
+    send.send(1u8).unwrap();
+    assert_eq!(child.wait(&mut [0]), &mut [recv.id()][..]);
+    assert_eq!(parent.wait(&mut [0]), &mut [][..]);
+}
+
+#[test]
+fn wait_auto_prune() {
+    let mut sends = vec!();
+    let mut recvs = vec!();
+    for _ in 0..4 {
+        let (send, recv) = new::<u8>();
+        sends.push(Some(send));
+        recvs.push(recv);
+    }
+    let select = Select::new();
+    for recv in &recvs {
+        select.add(recv);
+    }
+
+    sends[1].take();
+    sends[3].take();
+    sends[0].as_ref().unwrap().send(1).unwrap();
+
+    let mut ids = [recvs[0].id(), recvs[1].id(), recvs[3].id()];
+    ids.sort();
+    let mut buf = [0; 4];
+    assert_eq!(select.wait_auto_prune(&mut buf), &mut ids[..]);
+
+    let mut ids = select.target_ids();
+    ids.sort();
+    let mut expected = [recvs[0].id(), recvs[2].id()];
+    expected.sort();
+    assert_eq!(ids, expected);
+
+    assert_eq!(recvs[0].recv_async().unwrap(), 1);
+    assert_eq!(select.wait_timeout(&mut buf, None), Some(&mut [][..]));
+}
+
+#[test]
+fn wait_with_status() {
+    let (send, recv) = new::<u8>();
+    let (send2, recv2) = new::<u8>();
+    let (send3, recv3) = new::<u8>();
+    let select = Select::new();
+    select.add(&recv);
+    select.add(&recv2);
+    select.add(&recv3);
+
+    send.send(1).unwrap();
+    drop(send2);
+
+    let mut expected = [(recv.id(), Status::Ready), (recv2.id(), Status::Disconnected)];
+    expected.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut buf = [(0, Status::Ready); 3];
+    assert_eq!(select.wait_with_status(&mut buf), &mut expected[..]);
+}
+
+#[test]
+fn wait_all() {
+    let (send, recv) = new::<u8>();
+    let (send2, recv2) = new::<u8>();
+    let (send3, recv3) = new::<u8>();
+    let select = Select::new();
+    select.add(&recv);
+    select.add(&recv2);
+    select.add(&recv3);
+
+    let mut expected = [recv.id(), recv2.id(), recv3.id()];
+    expected.sort();
+
+    // recv becomes ready and is drained again before the others become ready.
+    send.send(1).unwrap();
+    let _t = thread::scoped(move || {
+        ms_sleep(100);
+        assert_eq!(recv.recv_sync().unwrap(), 1);
+        ms_sleep(100);
+        send2.send(2).unwrap();
+        ms_sleep(100);
+        send3.send(3).unwrap();
+        recv
+    });
+
+    let mut buf = [0; 3];
+    assert_eq!(select.wait_all(&mut buf), &mut expected[..]);
+    assert_eq!(recv2.recv_async().unwrap(), 2);
+    assert_eq!(recv3.recv_async().unwrap(), 3);
+}
+
+// A target that is never ready but notifies the `Select` objects it's registered with on
+// request, like a channel whose messages are received by someone else right away.
+struct Pulse<'a> {
+    wait_queue: Mutex<WaitQueue<'a>>,
+}
+
+unsafe impl<'a> Send for Pulse<'a> { }
+unsafe impl<'a> Sync for Pulse<'a> { }
+
+unsafe impl<'a> _Selectable<'a> for Pulse<'a> {
+    fn ready(&self) -> bool {
+        false
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        self.wait_queue.lock().unwrap().add(load);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.wait_queue.lock().unwrap().remove(id);
+    }
+}
+
+struct PulseHandle<'a>(arc::Arc<Pulse<'a>>);
+
+impl<'a> PulseHandle<'a> {
+    fn new() -> PulseHandle<'a> {
+        let pulse = arc::Arc::new(Pulse { wait_queue: Mutex::new(WaitQueue::new()) });
+        pulse.wait_queue.lock().unwrap().set_id(pulse.unique_id());
+        PulseHandle(pulse)
+    }
+
+    fn pulse(&self) {
+        self.0.wait_queue.lock().unwrap().notify();
+    }
+}
+
+impl<'a> Selectable<'a> for PulseHandle<'a> {
+    fn id(&self) -> usize {
+        self.0.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.0.as_trait(&*self.0 as &(_Selectable+'a)) }
+    }
+}
+
+#[test]
+fn wait_all_drained_while_waiting() {
+    let pulse = PulseHandle::new();
+    let (send, recv) = new::<u8>();
+    let select = Select::new();
+    select.add(&pulse);
+    select.add(&recv);
+
+    let mut expected = [pulse.id(), recv.id()];
+    expected.sort();
+
+    let _t = thread::scoped(|| {
+        ms_sleep(100);
+        pulse.pulse();
+        ms_sleep(100);
+        send.send(1).unwrap();
+    });
+
+    let mut buf = [0; 2];
+    assert_eq!(select.wait_all(&mut buf), &mut expected[..]);
+}
+
+#[test]
+fn wait_timeout_expires() {
+    let (_send, recv) = new::<u8>();
+    let select = Select::new();
+    select.add(&recv);
+    assert_eq!(select.wait_timeout(&mut [0], Some(Duration::from_millis(10))), None);
+}
+
+#[test]
+fn wait_timeout_ready() {
+    let (send, recv) = new();
+    let select = Select::new();
+    select.add(&recv);
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send(1u8).unwrap();
+    });
+    assert_eq!(select.wait_timeout(&mut [0], Some(Duration::from_millis(1000))),
+               Some(&mut [recv.id()][..]));
+}
+
+#[test]
+fn recv_any_ready() {
+    let (send, recv) = new::<u8>();
+    let (send2, recv2) = new::<u8>();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send2.send(2).unwrap();
+    });
+
+    assert_eq!(recv_any(&[&recv, &recv2]), (1, Ok(2)));
+
+    drop(send);
+    assert_eq!(recv_any(&[&recv]), (0, Err(Error::Disconnected)));
+}
+
+#[test]
+fn policy_round_robin() {
+    let chans: Vec<_> = (0..3).map(|_| new::<u8>()).collect();
+    let select = Select::new_with_policy(Policy::RoundRobin);
+    for &(ref send, ref recv) in &chans {
+        send.send(1).unwrap();
+        select.add(recv);
+    }
+    let mut ids: Vec<_> = chans.iter().map(|c| c.1.id()).collect();
+    ids.sort();
+
+    for i in 0..6 {
+        assert_eq!(select.wait(&mut [0])[0], ids[i % 3]);
+    }
+    assert_eq!(select.wait(&mut [0, 0]), &mut [ids[0], ids[1]][..]);
+    assert_eq!(select.wait(&mut [0, 0]), &mut [ids[1], ids[2]][..]);
+}
+
+#[test]
+fn policy_random() {
+    let chans: Vec<_> = (0..3).map(|_| new::<u8>()).collect();
+    let select = Select::new_with_policy(Policy::Random { seed: 1 });
+    let select2 = Select::new_with_policy(Policy::Random { seed: 1 });
+    for &(ref send, ref recv) in &chans {
+        send.send(1).unwrap();
+        select.add(recv);
+        select2.add(recv);
+    }
+
+    let mut seen = vec!();
+    for _ in 0..100 {
+        let id = select.wait(&mut [0])[0];
+        assert_eq!(select2.wait(&mut [0])[0], id);
+        if !seen.contains(&id) {
+            seen.push(id);
+        }
+    }
+    assert_eq!(seen.len(), 3);
+}
+
+#[test]
+fn fair_small_buffer() {
+    let chans: Vec<_> = (0..3).map(|_| new::<u8>()).collect();
+    let select = Select::new();
+    for &(ref send, ref recv) in &chans {
+        send.send(1).unwrap();
+        select.add(recv);
+    }
+
+    let mut seen = vec!();
+    for _ in 0..3 {
+        let id = select.wait(&mut [0])[0];
+        assert!(!seen.contains(&id));
+        seen.push(id);
+    }
+    for &(_, ref recv) in &chans {
+        assert!(seen.contains(&recv.id()));
+    }
+}
+
+#[test]
+fn notify_after_wait() {
+    let (send, recv) = new();
+    let select = Select::new();
+    select.add(&recv);
+    let mut buf = [0];
+    for i in 0..3 {
+        send.send(i as u8).unwrap();
+        send.send(i as u8).unwrap();
+        assert_eq!(select.wait(&mut buf), &mut [recv.id()][..]);
+        assert_eq!(recv.recv_sync().unwrap(), i);
+        assert_eq!(recv.recv_sync().unwrap(), i);
+        assert_eq!(select.wait_timeout(&mut buf, None), Some(&mut [][..]));
+    }
+}
+
+#[test]
+fn nested_notify_after_wait() {
+    let (send, recv) = new();
+    let child = Select::new();
+    child.add(&recv);
+    let parent = Select::new();
+    parent.add(&child);
+    let mut buf = [0];
+    for i in 0..3 {
+        send.send(i as u8).unwrap();
+        send.send(i as u8).unwrap();
+        assert_eq!(parent.wait(&mut buf), &mut [child.id()][..]);
+        assert_eq!(recv.recv_sync().unwrap(), i);
+        assert_eq!(recv.recv_sync().unwrap(), i);
+        assert_eq!(parent.wait_timeout(&mut buf, None), Some(&mut [][..]));
+    }
+}
+
+#[test]
+fn std_receiver() {
+    let (send, recv) = ::std::sync::mpsc::channel();
+    let recv = StdReceiver::new(recv);
+    let select = Select::new();
+    select.add(&recv);
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send(1u8).unwrap();
+    });
+
+    let mut buf = [0];
+    assert_eq!(select.wait(&mut buf), &mut [recv.id()][..]);
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn add_with_priority() {
+    let mut chans = vec!(new::<u8>(), new::<u8>());
+    // Make sure that the default order would report the low priority target first.
+    chans.sort_by(|a, b| a.1.id().cmp(&b.1.id()));
+    let (send2, recv2) = chans.pop().unwrap();
+    let (send, recv) = chans.pop().unwrap();
+    let select = Select::new();
+    select.add(&recv);
+    select.add_with_priority(&recv2, 1);
+
+    send.send(1).unwrap();
+    send2.send(2).unwrap();
+    for _ in 0..3 {
+        assert_eq!(select.wait(&mut [0]), &mut [recv2.id()][..]);
+    }
+    assert_eq!(select.wait(&mut [0, 0]), &mut [recv2.id(), recv.id()][..]);
+
+    select.add_with_priority(&recv, 2);
+    assert_eq!(select.wait(&mut [0]), &mut [recv.id()][..]);
+    assert_eq!(select.len(), 2);
+
+    // Adding the target again without a priority keeps its priority.
+    select.add(&recv);
+    select.add_keyed(&recv, 1);
+    assert_eq!(select.wait(&mut [0]), &mut [recv.id()][..]);
+}
+
+#[test]
+fn prioritized_count() {
+    let (send, recv) = new::<u8>();
+    let (_send2, recv2) = new::<u8>();
+    let (send3, recv3) = new::<u8>();
+    let select = Select::new();
+    select.add_with_priority(&recv, 1);
+    select.add_with_priority(&recv2, 2);
+    select.add_with_priority(&recv3, 3);
+    assert_eq!(select.prioritized_count(), 3);
+
+    select.add_with_priority(&recv2, 0);
+    assert_eq!(select.prioritized_count(), 2);
+    select.add_with_priority(&recv2, 1);
+    assert_eq!(select.prioritized_count(), 3);
+
+    select.remove(&recv2);
+    assert_eq!(select.prioritized_count(), 3);
+    select.remove(&recv2);
+    select.remove(&recv2);
+    assert_eq!(select.prioritized_count(), 2);
+
+    drop(send);
+    select.wait_auto_prune(&mut [0, 0]);
+    assert_eq!(select.prioritized_count(), 1);
+
+    drop(send3);
+    drop(recv3);
+    assert_eq!(select.prioritized_count(), 0);
+}
+
+#[test]
+fn iter_with() {
+    let select = Select::new();
+    let mut consumers = HashMap::new();
+    let mut producers = vec!();
+    for _ in 0..3 {
+        let (send, recv) = new::<u8>();
+        select.add(&recv);
+        producers.push(send);
+        consumers.insert(recv.id(), recv);
+    }
+
+    thread::spawn(move || {
+        for send in producers {
+            ms_sleep(10);
+            send.send(0).unwrap();
+            send.send(1).unwrap();
+        }
+    });
+
+    let mut received: Vec<_> = select.iter_with(&consumers).collect();
+    received.sort();
+    let mut ids: Vec<_> = consumers.keys().cloned().collect();
+    ids.sort();
+    let expected: Vec<_> = ids.iter().flat_map(|&id| vec!((id, 0), (id, 1))).collect();
+    assert_eq!(received, expected);
+    assert!(select.is_empty());
+}
+
+#[test]
+fn poll() {
+    let select = Select::new();
+    let mut buf = [0];
+    assert_eq!(select.poll(&mut buf), PollResult::Empty);
+
+    let (send, recv) = new();
+    select.add(&recv);
+    assert_eq!(select.poll(&mut buf), PollResult::WouldBlock);
+    assert_eq!(select.wait_timeout(&mut buf, None), Some(&mut [][..]));
+
+    send.send(1u8).unwrap();
+    assert_eq!(select.poll(&mut buf), PollResult::Ready(&mut [recv.id()]));
+    assert_eq!(select.wait_timeout(&mut buf, None), Some(&mut [recv.id()][..]));
+}
+
+#[test]
+fn ready_capacity_bounded() {
+    let select = Select::new();
+    let mut channels: Vec<_> = (0..1000).map(|_| new()).collect();
+    for &(ref send, ref recv) in &channels {
+        select.add(recv);
+        send.send(1u8).unwrap();
+    }
+    let mut buf = vec!(0; 1000);
+    assert_eq!(select.wait(&mut buf).len(), 1000);
+    assert!(select.ready_capacity() >= 1000);
+
+    for (_send, recv) in channels.drain(1..) {
+        select.remove(&recv);
+    }
+    let (ref send, ref recv) = channels[0];
+    recv.recv_async().unwrap();
+    for _ in 0..3 {
+        send.send(1u8).unwrap();
+        assert_eq!(select.wait(&mut buf), &mut [recv.id()][..]);
+        recv.recv_async().unwrap();
+    }
+    assert!(select.ready_capacity() < 1000);
+}
+
+#[test]
+fn wait_keyed() {
+    let (send1, recv1) = new();
+    let (send2, recv2) = new();
+    let (send3, recv3) = new();
+
+    let select = Select::new();
+    select.add_keyed(&recv1, 10);
+    select.add_keyed(&recv2, 11);
+    select.add(&recv3);
+
+    let mut keys = [None; 3];
+    send2.send(1u8).unwrap();
+    assert_eq!(select.wait_keyed(&mut keys), &mut [Some(11)][..]);
+    recv2.recv_async().unwrap();
+
+    send3.send(1u8).unwrap();
+    assert_eq!(select.wait_keyed(&mut keys), &mut [Some(recv3.id() as u64)][..]);
+    recv3.recv_async().unwrap();
+
+    // Adding the target again updates the key.
+    select.add_keyed(&recv1, 12);
+    send1.send(1u8).unwrap();
+    assert_eq!(select.wait_keyed(&mut keys), &mut [Some(12)][..]);
+    recv1.recv_async().unwrap();
+
+    // A target that goes away while we're waiting no longer has a key.
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(send2);
+        drop(recv2);
+    });
+    assert_eq!(select.wait_keyed(&mut keys), &mut [None][..]);
+}
+
+#[test]
+fn wait_vec() {
+    let select = Select::new();
+    assert_eq!(select.wait_vec(), vec!());
+
+    let channels: Vec<_> = (0..100).map(|_| new()).collect();
+    for &(_, ref recv) in &channels {
+        select.add(recv);
+    }
+    let mut expected = vec!();
+    for (i, &(ref send, ref recv)) in channels.iter().enumerate() {
+        if i % 2 == 0 {
+            send.send(1u8).unwrap();
+            expected.push(recv.id());
+        }
+    }
+    expected.sort();
+    assert_eq!(select.wait_vec(), expected);
+}
+
+#[test]
+fn select_macro() {
+    let (send1, recv1) = new();
+    let (send2, recv2) = new();
+
+    send2.send("two").unwrap();
+    let rv = select! {
+        n = recv1 => n.map(|n: u8| n as usize),
+        s = recv2 => s.map(|s| s.len()),
+    };
+    assert_eq!(rv, Ok(3));
+
+    let t = thread::scoped(|| {
+        ms_sleep(100);
+        send1.send(1u8).unwrap();
+    });
+    let rv = select! {
+        n = recv1 => n.map(|n| n as usize),
+        s = recv2 => s.map(|s| s.len()),
+    };
+    assert_eq!(rv, Ok(1));
+    drop(t);
+
+    drop(send2);
+    let rv = select! {
+        _n = recv1 => panic!(),
+        s = recv2 => s,
+    };
+    assert_eq!(rv, Err(Error::Disconnected));
+}
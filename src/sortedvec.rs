@@ -1,68 +1,96 @@
+//! A vector that keeps its elements sorted and free of duplicates.
+//!
+//! This is the structure `Select` uses to store its ready list.
+
 use std::ops::{Deref, DerefMut};
+use std::{mem, slice, vec};
 
+/// A vector whose elements are sorted in ascending order and pairwise distinct.
+///
+/// The vector can be accessed as a `Vec<T>` via `Deref` and `DerefMut`. Code that
+/// modifies the vector this way is responsible for upholding the invariants above.
 pub struct SortedVec<T: Ord> {
     data: Vec<T>,
 }
 
 impl<T: Ord> SortedVec<T> {
+    /// Creates a new, empty vector.
     pub fn new() -> SortedVec<T> {
         SortedVec {
             data: vec!(),
         }
     }
 
-    pub fn insert(&mut self, val: T) {
+    /// Searches for `val`.
+    ///
+    /// Returns `Ok(pos)` if `val` is stored at position `pos` and `Err(pos)` if `val` is
+    /// not in the vector, where `pos` is the position at which `val` would have to be
+    /// inserted.
+    pub fn binary_search(&self, val: &T) -> Result<usize, usize> {
         let mut left = 0;
         let mut right = self.data.len();
 
         while left != right {
             let (middle, rem) = ((left + right) / 2, (left + right) % 2);
-            if val <= self.data[middle] {
+            if val <= &self.data[middle] {
                 right = middle;
             } else {
                 left = middle + rem;
             }
         }
 
-        if self.data.len() == 0 || left == self.data.len() || self.data[left] != val {
-            self.data.insert(left, val);
+        if left < self.data.len() && &self.data[left] == val {
+            Ok(left)
+        } else {
+            Err(left)
         }
     }
 
-    pub fn contains(&self, val: &T) -> bool {
-        let mut left = 0;
-        let mut right = self.data.len();
+    /// Inserts `val` unless an equal element is already in the vector.
+    ///
+    /// Returns whether `val` was inserted. If it was not inserted, it is dropped and the
+    /// vector is not modified.
+    pub fn insert(&mut self, val: T) -> bool {
+        match self.binary_search(&val) {
+            Ok(..) => false,
+            Err(pos) => {
+                self.data.insert(pos, val);
+                true
+            },
+        }
+    }
 
-        while left != right {
-            let (middle, rem) = ((left + right) / 2, (left + right) % 2);
-            if val <= &self.data[middle] {
-                right = middle;
-            } else {
-                left = middle + rem;
-            }
+    /// Inserts `val`, replacing an equal element if one is already in the vector.
+    ///
+    /// Returns the replaced element.
+    pub fn insert_or_replace(&mut self, mut val: T) -> Option<T> {
+        match self.binary_search(&val) {
+            Ok(pos) => {
+                mem::swap(&mut self.data[pos], &mut val);
+                Some(val)
+            },
+            Err(pos) => {
+                self.data.insert(pos, val);
+                None
+            },
         }
+    }
 
-        left < self.data.len() && &self.data[left] == val
+    /// Returns whether an element equal to `val` is in the vector.
+    pub fn contains(&self, val: &T) -> bool {
+        self.binary_search(val).is_ok()
     }
 
+    /// Removes the element equal to `val` from the vector.
+    ///
+    /// Returns whether such an element was in the vector.
     pub fn remove(&mut self, val: &T) -> bool {
-        let mut left = 0;
-        let mut right = self.data.len();
-
-        while left != right {
-            let (middle, rem) = ((left + right) / 2, (left + right) % 2);
-            if val <= &self.data[middle] {
-                right = middle;
-            } else {
-                left = middle + rem;
-            }
-        }
-
-        if left < self.data.len() && &self.data[left] == val {
-            self.data.remove(left);
-            true
-        } else {
-            false
+        match self.binary_search(val) {
+            Ok(pos) => {
+                self.data.remove(pos);
+                true
+            },
+            Err(..) => false,
         }
     }
 }
@@ -80,3 +108,97 @@ impl<T: Ord> DerefMut for SortedVec<T> {
         &mut self.data
     }
 }
+
+impl<T: Ord> IntoIterator for SortedVec<T> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    fn into_iter(self) -> vec::IntoIter<T> {
+        self.data.into_iter()
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a SortedVec<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.data.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::{Ordering};
+
+    use super::{SortedVec};
+
+    #[test]
+    fn insert() {
+        let mut vec = SortedVec::new();
+        assert!(vec.insert(3));
+        assert!(vec.insert(1));
+        assert!(vec.insert(2));
+        assert!(!vec.insert(2));
+        assert_eq!(&*vec, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn insert_or_replace() {
+        #[derive(Debug)]
+        struct E(u8, u8);
+
+        impl PartialEq for E {
+            fn eq(&self, other: &E) -> bool { self.0 == other.0 }
+        }
+        impl Eq for E { }
+        impl PartialOrd for E {
+            fn partial_cmp(&self, other: &E) -> Option<Ordering> {
+                self.0.partial_cmp(&other.0)
+            }
+        }
+        impl Ord for E {
+            fn cmp(&self, other: &E) -> Ordering { self.0.cmp(&other.0) }
+        }
+
+        let mut vec = SortedVec::new();
+        assert!(vec.insert_or_replace(E(1, 0)).is_none());
+        assert_eq!(vec.insert_or_replace(E(1, 1)).unwrap().1, 0);
+        assert_eq!(vec.len(), 1);
+        assert_eq!(vec[0].1, 1);
+    }
+
+    #[test]
+    fn remove() {
+        let mut vec = SortedVec::new();
+        vec.insert(1);
+        vec.insert(2);
+        assert!(!vec.remove(&3));
+        assert!(vec.remove(&1));
+        assert!(!vec.remove(&1));
+        assert_eq!(&*vec, &[2]);
+    }
+
+    #[test]
+    fn binary_search() {
+        let mut vec = SortedVec::new();
+        assert_eq!(vec.binary_search(&1), Err(0));
+        assert!(!vec.contains(&1));
+        vec.insert(1);
+        vec.insert(3);
+        assert_eq!(vec.binary_search(&0), Err(0));
+        assert_eq!(vec.binary_search(&1), Ok(0));
+        assert_eq!(vec.binary_search(&2), Err(1));
+        assert_eq!(vec.binary_search(&3), Ok(1));
+        assert_eq!(vec.binary_search(&4), Err(2));
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut vec = SortedVec::new();
+        vec.insert(2);
+        vec.insert(1);
+        assert_eq!((&vec).into_iter().cloned().collect::<Vec<_>>(), vec!(1, 2));
+        assert_eq!(vec.into_iter().collect::<Vec<_>>(), vec!(1, 2));
+    }
+}
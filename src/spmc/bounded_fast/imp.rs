@@ -128,7 +128,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.notify_wait_queue();
     }
 
-    fn notify_wait_queue(&self) {
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
         if self.wait_queue_used.load(SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -284,6 +285,18 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
 
         rv
     }
+
+    pub fn capacity(&self) -> usize {
+        self.cap_mask + 1
+    }
+
+    pub fn sender_count(&self) -> usize {
+        !self.sender_disconnected.load(SeqCst) as usize
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.num_receivers.load(SeqCst)
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
@@ -311,6 +324,15 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         node.pos.load(SeqCst) as isize - 1 - next_read as isize >= 0
     }
 
+    fn is_disconnected(&self) -> bool {
+        if !self.sender_disconnected.load(SeqCst) {
+            return false;
+        }
+        let next_read = self.next_read.load(SeqCst);
+        let node = self.get_node(next_read);
+        node.pos.load(SeqCst) as isize - 1 - next_read as isize < 0
+    }
+
     fn register(&self, load: Payload<'a>) {
         let mut wait_queue = self.wait_queue.lock().unwrap();
         if wait_queue.add(load) > 0 {
@@ -1,7 +1,9 @@
 //! A bounded SPMC channel.
 
+use std::{fmt};
+
 use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
+use select::{Selectable, RecvSelectable, _Selectable};
 use {Error, Sendable};
 
 mod imp;
@@ -44,6 +46,20 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
         self.data.send_async(val, false)
     }
+
+    /// Returns the number of producers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn sender_count(&self) -> usize {
+        self.data.sender_count()
+    }
+
+    /// Returns the number of consumers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn receiver_count(&self) -> usize {
+        self.data.receiver_count()
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Producer<'a, T> { }
@@ -78,6 +94,34 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn recv_async(&self) -> Result<T, Error> {
         self.data.recv_async(false)
     }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns the number of producers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn sender_count(&self) -> usize {
+        self.data.sender_count()
+    }
+
+    /// Returns the number of consumers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn receiver_count(&self) -> usize {
+        self.data.receiver_count()
+    }
+
+    /// Returns whether the sender has disconnected and the channel is empty.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
@@ -104,3 +148,27 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spmc::bounded_fast::Producer")
+         .field("id", &self.data.unique_id())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spmc::bounded_fast::Consumer")
+         .field("id", &self.data.unique_id())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
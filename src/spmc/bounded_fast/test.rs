@@ -164,3 +164,16 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn receiver_count() {
+    let (send, recv) = unsafe { super::new::<u8>(2) };
+    assert_eq!(recv.receiver_count(), 1);
+    let recv2 = recv.clone();
+    assert_eq!(send.receiver_count(), 2);
+    drop(recv2);
+    assert_eq!(send.receiver_count(), 1);
+    assert_eq!(recv.sender_count(), 1);
+    drop(send);
+    assert_eq!(recv.sender_count(), 0);
+}
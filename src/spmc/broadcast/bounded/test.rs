@@ -0,0 +1,150 @@
+use std::sync::{Arc};
+use std::thread::{self, sleep_ms};
+use std::sync::atomic::{AtomicUsize};
+use std::sync::atomic::Ordering::{SeqCst};
+
+use select::{Select, Selectable};
+use {Error};
+
+fn ms_sleep(ms: i64) {
+    sleep_ms(ms as u32);
+}
+
+#[test]
+fn send_recv() {
+    let (send, recv) = super::new(2);
+    let recv2 = recv.clone();
+    send.send_async(1u8).unwrap();
+    send.send_async(2).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.recv_async().unwrap(), 2);
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
+    assert_eq!(recv2.recv_async().unwrap(), 1);
+    assert_eq!(recv2.recv_async().unwrap(), 2);
+    assert_eq!(recv2.recv_async().unwrap_err(), Error::Empty);
+}
+
+#[test]
+fn full() {
+    let (send, recv) = super::new(2);
+    let recv2 = recv.clone();
+    send.send_async(1u8).unwrap();
+    send.send_async(2).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.recv_async().unwrap(), 2);
+    // `recv2` hasn't received anything yet.
+    assert_eq!(send.send_async(3).unwrap_err(), (3, Error::Full));
+    assert_eq!(recv2.recv_async().unwrap(), 1);
+    send.send_async(3).unwrap();
+    assert_eq!(send.send_async(4).unwrap_err(), (4, Error::Full));
+}
+
+#[test]
+fn slow_consumer_throttles() {
+    let (send, recv) = super::new(1);
+    let slow = recv.clone();
+    let received = Arc::new(AtomicUsize::new(0));
+    let received2 = received.clone();
+
+    thread::spawn(move || {
+        while let Ok(_) = recv.recv_sync() { }
+    });
+    thread::spawn(move || {
+        for i in 0..3 {
+            ms_sleep(100);
+            received2.fetch_add(1, SeqCst);
+            assert_eq!(slow.recv_sync().unwrap(), i);
+        }
+    });
+
+    send.send_sync(0u8).unwrap();
+    send.send_sync(1).unwrap();
+    assert!(received.load(SeqCst) >= 1);
+    send.send_sync(2).unwrap();
+    assert!(received.load(SeqCst) >= 2);
+}
+
+#[test]
+fn drop_send() {
+    let (send, recv) = super::new(2);
+    send.send_async(1u8).unwrap();
+    drop(send);
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn drop_recv() {
+    let (send, recv) = super::new(1);
+    let recv2 = recv.clone();
+    send.send_async(1u8).unwrap();
+    drop(recv);
+    assert_eq!(send.send_async(2).unwrap_err(), (2, Error::Full));
+    drop(recv2);
+    assert_eq!(send.send_async(2).unwrap_err(), (2, Error::Disconnected));
+}
+
+#[test]
+fn drop_slow_recv_unblocks() {
+    let (send, recv) = super::new(1);
+    let slow = recv.clone();
+    send.send_sync(1u8).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(slow);
+    });
+    send.send_sync(2).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 2);
+}
+
+#[test]
+fn subscribe() {
+    let (send, recv) = super::new(2);
+    send.send_async(1u8).unwrap();
+    let recv2 = send.subscribe();
+    send.send_async(2).unwrap();
+    assert_eq!(recv2.recv_async().unwrap(), 2);
+    assert_eq!(recv2.recv_async().unwrap_err(), Error::Empty);
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.recv_async().unwrap(), 2);
+}
+
+#[test]
+fn select() {
+    let (send, recv) = super::new(2);
+    let recv2 = recv.clone();
+    send.send_async(1u8).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 1);
+
+    let select = Select::new();
+    select.add(&recv);
+    select.add(&recv2);
+
+    let mut buf = [0, 0];
+    assert_eq!(select.wait(&mut buf), &mut [recv2.id()][..]);
+
+    assert_eq!(recv2.recv_async().unwrap(), 1);
+    assert_eq!(select.wait_timeout(&mut buf, None), Some(&mut [][..]));
+
+    send.send_async(2).unwrap();
+    let mut ids = [recv.id(), recv2.id()];
+    ids.sort();
+    assert_eq!(select.wait(&mut buf), &mut ids[..]);
+}
+
+#[test]
+fn select_wait() {
+    let (send, recv) = super::new(2);
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send_async(1u8).unwrap();
+    });
+
+    let select = Select::new();
+    select.add(&recv);
+
+    let mut buf = [0];
+    assert_eq!(select.wait(&mut buf), &mut [recv.id()][..]);
+}
@@ -0,0 +1,281 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, Condvar};
+
+use arc::{Arc};
+use select::{_Selectable, WaitQueue, Payload};
+use {Error, Sendable};
+
+/// The packet shared by the bounded and the unbounded broadcast channel.
+pub struct Packet<'a, T: Sendable+'a> {
+    // The maximum number of messages a receiver can fall behind the sender or `None` if
+    // the channel is unbounded.
+    cap: Option<usize>,
+
+    inner: Mutex<Inner<T>>,
+
+    // Condvar the sender of a bounded channel is waiting on.
+    send_condvar: Condvar,
+    // Condvar the receivers are waiting on.
+    recv_condvar: Condvar,
+
+    // The wait queues of the receivers, indexed by their tokens. This is not part of
+    // `inner` because `ready` acquires the `inner` lock and therefore we must never hold
+    // it while we interact with a wait queue.
+    wait_queues: Mutex<HashMap<usize, WaitQueue<'a>>>,
+}
+
+struct Inner<T> {
+    // The messages that haven't been received by all receivers yet. `buf[0]` is the
+    // message at position `start`.
+    buf: VecDeque<T>,
+    start: usize,
+
+    // The receivers, indexed by their tokens, and the position of the next message they
+    // receive.
+    cursors: HashMap<usize, usize>,
+    // The token we give to the next receiver.
+    next_token: usize,
+
+    // Has the sender disconnected?
+    sender_disconnected: bool,
+}
+
+impl<T> Inner<T> {
+    fn write_pos(&self) -> usize {
+        self.start + self.buf.len()
+    }
+
+    // Drops the messages that have been received by all receivers. Returns whether any
+    // messages were dropped.
+    fn trim(&mut self) -> bool {
+        let min = self.cursors.values().cloned().min().unwrap_or(self.write_pos());
+        let old_start = self.start;
+        while self.start < min {
+            self.buf.pop_front();
+            self.start += 1;
+        }
+        self.start != old_start
+    }
+}
+
+impl<'a, T: Sendable+'a> Packet<'a, T> {
+    pub fn new(cap: Option<usize>) -> Packet<'a, T> {
+        assert!(cap != Some(0), "capacity must be positive");
+        Packet {
+            cap: cap,
+
+            inner: Mutex::new(Inner {
+                buf: VecDeque::with_capacity(cap.unwrap_or(0)),
+                start: 0,
+                cursors: HashMap::new(),
+                next_token: 0,
+                sender_disconnected: false,
+            }),
+
+            send_condvar: Condvar::new(),
+            recv_condvar: Condvar::new(),
+
+            wait_queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a new receiver and returns its token. The receiver starts at the position of
+    /// the receiver with token `from` or, if `from` is `None`, at the next message that
+    /// will be sent.
+    pub fn add_receiver(&self, from: Option<usize>) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let pos = match from {
+            Some(token) => inner.cursors[&token],
+            _ => inner.write_pos(),
+        };
+        let token = inner.next_token;
+        inner.next_token += 1;
+        inner.cursors.insert(token, pos);
+        token
+    }
+
+    /// Call this after `add_receiver` with the id of the receiver's `Selectable` object.
+    pub fn set_receiver_id(&self, token: usize, id: usize) {
+        let mut wait_queue = WaitQueue::new();
+        wait_queue.set_id(id);
+        self.wait_queues.lock().unwrap().insert(token, wait_queue);
+    }
+
+    /// Call this when a receiver gets dropped.
+    pub fn remove_receiver(&self, token: usize) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.cursors.remove(&token);
+            if inner.trim() || inner.cursors.is_empty() {
+                self.send_condvar.notify_one();
+            }
+        }
+
+        let wait_queue = self.wait_queues.lock().unwrap().remove(&token);
+        // Dropping the wait queue notifies the `Select` objects.
+        drop(wait_queue);
+    }
+
+    /// Call this when the sender gets dropped.
+    pub fn disconnect_sender(&self) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.sender_disconnected = true;
+            self.recv_condvar.notify_all();
+        }
+        self.notify_wait_queues();
+    }
+
+    /// Notifies everyone selecting on any of the receivers.
+    fn notify_wait_queues(&self) {
+        let mut wait_queues = self.wait_queues.lock().unwrap();
+        for wait_queue in wait_queues.values_mut() {
+            wait_queue.notify();
+        }
+    }
+
+    /// Sends a message to all receivers. Fails with `Full` if the channel is bounded and
+    /// a receiver has `cap` messages it hasn't received yet.
+    pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.cursors.is_empty() {
+                return Err((val, Error::Disconnected));
+            }
+            if self.is_full(&inner) {
+                return Err((val, Error::Full));
+            }
+            inner.buf.push_back(val);
+            self.recv_condvar.notify_all();
+        }
+        self.notify_wait_queues();
+        Ok(())
+    }
+
+    /// Like `send_async` but waits until there is space in the channel. Never blocks if
+    /// the channel is unbounded.
+    pub fn send_sync(&self, val: T) -> Result<(), (T, Error)> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            loop {
+                if inner.cursors.is_empty() {
+                    return Err((val, Error::Disconnected));
+                }
+                if !self.is_full(&inner) {
+                    break;
+                }
+                inner = self.send_condvar.wait(inner).unwrap();
+            }
+            inner.buf.push_back(val);
+            self.recv_condvar.notify_all();
+        }
+        self.notify_wait_queues();
+        Ok(())
+    }
+
+    pub fn ready(&self, token: usize) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.sender_disconnected || inner.cursors[&token] < inner.write_pos()
+    }
+
+    pub fn is_drained(&self, token: usize) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.sender_disconnected && inner.cursors[&token] == inner.write_pos()
+    }
+
+    pub fn register(&self, token: usize, load: Payload<'a>) {
+        let mut wait_queues = self.wait_queues.lock().unwrap();
+        wait_queues.get_mut(&token).unwrap().add(load);
+    }
+
+    pub fn unregister(&self, token: usize, id: usize) {
+        let mut wait_queues = self.wait_queues.lock().unwrap();
+        wait_queues.get_mut(&token).unwrap().remove(id);
+    }
+
+    fn is_full(&self, inner: &Inner<T>) -> bool {
+        self.cap.map(|cap| inner.buf.len() == cap).unwrap_or(false)
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.cap
+    }
+}
+
+impl<'a, T: Sendable+Clone+'a> Packet<'a, T> {
+    fn recv_locked(&self, inner: &mut Inner<T>, token: usize) -> Result<T, Error> {
+        let pos = inner.cursors[&token];
+        if pos == inner.write_pos() {
+            return if inner.sender_disconnected {
+                Err(Error::Disconnected)
+            } else {
+                Err(Error::Empty)
+            };
+        }
+        inner.cursors.insert(token, pos + 1);
+
+        let val = if pos == inner.start && inner.cursors.values().all(|&p| p > pos) {
+            // We were the last receiver that hadn't received the oldest message. Take it
+            // instead of cloning it. This frees a slot for the sender.
+            inner.start += 1;
+            self.send_condvar.notify_one();
+            inner.buf.pop_front().unwrap()
+        } else {
+            inner.buf[pos - inner.start].clone()
+        };
+
+        Ok(val)
+    }
+
+    pub fn recv_async(&self, token: usize) -> Result<T, Error> {
+        let mut inner = self.inner.lock().unwrap();
+        self.recv_locked(&mut inner, token)
+    }
+
+    pub fn recv_sync(&self, token: usize) -> Result<T, Error> {
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            match self.recv_locked(&mut inner, token) {
+                Err(Error::Empty) => { },
+                rv => return rv,
+            }
+            inner = self.recv_condvar.wait(inner).unwrap();
+        }
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Packet<'a, T> { }
+
+/// The part of a receiver that is shared with `Select` objects.
+pub struct Receiver<'a, T: Sendable+'a> {
+    pub packet: Arc<Packet<'a, T>>,
+    pub token: usize,
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Receiver<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Receiver<'a, T> { }
+
+impl<'a, T: Sendable+'a> Drop for Receiver<'a, T> {
+    fn drop(&mut self) {
+        self.packet.remove_receiver(self.token);
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Receiver<'a, T> {
+    fn ready(&self) -> bool {
+        self.packet.ready(self.token)
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.packet.is_drained(self.token)
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        self.packet.register(self.token, load);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.packet.unregister(self.token, id);
+    }
+}
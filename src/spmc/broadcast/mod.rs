@@ -0,0 +1,9 @@
+//! SPMC broadcast channels.
+//!
+//! In contrast to the other SPMC channels, every message sent over a broadcast channel is
+//! received by every consumer. Each consumer receives its own clone of the message.
+
+mod imp;
+
+pub mod bounded;
+pub mod unbounded;
@@ -0,0 +1,144 @@
+//! An unbounded SPMC broadcast channel.
+//!
+//! Every consumer has its own position in a shared log of messages and advances through
+//! it independently of the other consumers. A message is dropped from the log once every
+//! consumer has received it. The producer never blocks, so a slow consumer makes the log
+//! grow without bound.
+//!
+//! Messages are stored only once and are cloned when they are received. The consumer
+//! that receives a message last takes it without cloning it.
+
+use std::{fmt};
+
+use arc::{Arc, ArcTrait};
+use select::{Selectable, RecvSelectable, _Selectable};
+use {Error, Sendable};
+use super::{imp};
+
+#[cfg(test)] mod test;
+
+/// Creates a new unbounded SPMC broadcast channel.
+pub fn new<'a, T: Sendable+'a>() -> (Producer<'a, T>, Consumer<'a, T>) {
+    let packet = Arc::new(imp::Packet::new(None));
+    let token = packet.add_receiver(None);
+    (Producer { data: packet.clone() }, Consumer::new(packet, token))
+}
+
+/// The producing half of an unbounded SPMC broadcast channel.
+pub struct Producer<'a, T: Sendable+'a> {
+    data: Arc<imp::Packet<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> Producer<'a, T> {
+    /// Sends a message to all consumers. Never blocks.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All consumers have disconnected.
+    pub fn send(&self, val: T) -> Result<(), (T, Error)> {
+        self.data.send_async(val)
+    }
+
+    /// Creates a new consumer that receives all messages sent after this call.
+    pub fn subscribe(&self) -> Consumer<'a, T> {
+        let token = self.data.add_receiver(None);
+        Consumer::new(self.data.clone(), token)
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Producer<'a, T> { }
+
+impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
+    fn drop(&mut self) {
+        self.data.disconnect_sender();
+    }
+}
+
+/// The consuming half of an unbounded SPMC broadcast channel.
+///
+/// Cloning a consumer creates a new consumer that receives the same messages as the
+/// original consumer from now on, including those that the original consumer hasn't
+/// received yet.
+pub struct Consumer<'a, T: Sendable+'a> {
+    data: Arc<imp::Receiver<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> Consumer<'a, T> {
+    fn new(packet: Arc<imp::Packet<'a, T>>, token: usize) -> Consumer<'a, T> {
+        let data = Arc::new(imp::Receiver { packet: packet, token: token });
+        data.packet.set_receiver_id(token, data.unique_id());
+        Consumer { data: data }
+    }
+
+    /// Returns whether the sender has disconnected and the channel is empty.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
+}
+
+impl<'a, T: Sendable+Clone+'a> Consumer<'a, T> {
+    /// Receives a message from the channel. Blocks if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - There are no more messages for this consumer and the sender
+    ///   has disconnected.
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        self.data.packet.recv_sync(self.data.token)
+    }
+
+    /// Receives a message from the channel. Does not block if the channel is empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - There are no more messages for this consumer and the sender
+    ///   has disconnected.
+    /// - `Empty` - There are no more messages for this consumer.
+    pub fn recv_async(&self) -> Result<T, Error> {
+        self.data.packet.recv_async(self.data.token)
+    }
+}
+
+impl<'a, T: Sendable+'a> Clone for Consumer<'a, T> {
+    fn clone(&self) -> Consumer<'a, T> {
+        let token = self.data.packet.add_receiver(Some(self.data.token));
+        Consumer::new(self.data.packet.clone(), token)
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
+
+impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
+    fn id(&self) -> usize {
+        self.data.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
+    }
+}
+
+impl<'a, T: Sendable+Clone+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spmc::broadcast::unbounded::Producer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spmc::broadcast::unbounded::Consumer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
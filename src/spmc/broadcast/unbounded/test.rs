@@ -0,0 +1,111 @@
+use std::thread::{self, sleep_ms};
+
+use select::{Select, Selectable};
+use {Error};
+
+fn ms_sleep(ms: i64) {
+    sleep_ms(ms as u32);
+}
+
+#[test]
+fn send_recv() {
+    let (send, recv) = super::new();
+    let recv2 = recv.clone();
+    send.send(1u8).unwrap();
+    send.send(2).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.recv_async().unwrap(), 2);
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
+    assert_eq!(recv2.recv_async().unwrap(), 1);
+    assert_eq!(recv2.recv_async().unwrap(), 2);
+    assert_eq!(recv2.recv_async().unwrap_err(), Error::Empty);
+}
+
+#[test]
+fn never_full() {
+    let (send, recv) = super::new();
+    let recv2 = recv.clone();
+    for i in 0..1000 {
+        send.send(i).unwrap();
+    }
+    assert_eq!(recv.recv_async().unwrap(), 0);
+    for i in 0..1000 {
+        assert_eq!(recv2.recv_async().unwrap(), i);
+    }
+    for i in 1..1000 {
+        assert_eq!(recv.recv_async().unwrap(), i);
+    }
+}
+
+#[test]
+fn clone_continues_at_position() {
+    let (send, recv) = super::new();
+    send.send(1u8).unwrap();
+    send.send(2).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    let recv2 = recv.clone();
+    assert_eq!(recv2.recv_async().unwrap(), 2);
+    assert_eq!(recv.recv_async().unwrap(), 2);
+}
+
+#[test]
+fn drop_send() {
+    let (send, recv) = super::new();
+    send.send(1u8).unwrap();
+    drop(send);
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn drop_recv() {
+    let (send, recv) = super::new();
+    let recv2 = recv.clone();
+    drop(recv);
+    send.send(1u8).unwrap();
+    drop(recv2);
+    assert_eq!(send.send(2).unwrap_err(), (2, Error::Disconnected));
+}
+
+#[test]
+fn subscribe() {
+    let (send, recv) = super::new();
+    send.send(1u8).unwrap();
+    let recv2 = send.subscribe();
+    send.send(2).unwrap();
+    assert_eq!(recv2.recv_async().unwrap(), 2);
+    assert_eq!(recv2.recv_async().unwrap_err(), Error::Empty);
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.recv_async().unwrap(), 2);
+}
+
+#[test]
+fn sleep_send_recv() {
+    let (send, recv) = super::new();
+    let recv2 = recv.clone();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send(1u8).unwrap();
+    });
+
+    let t = thread::spawn(move || recv2.recv_sync().unwrap());
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(t.join().unwrap(), 1);
+}
+
+#[test]
+fn select_wait() {
+    let (send, recv) = super::new();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send(1u8).unwrap();
+    });
+
+    let select = Select::new();
+    select.add(&recv);
+
+    let mut buf = [0];
+    assert_eq!(select.wait(&mut buf), &mut [recv.id()][..]);
+}
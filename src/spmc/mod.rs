@@ -2,7 +2,9 @@
 //!
 //! An SPMC channel has exactly one producer and an arbitrary number of consumers which
 //! can be cloned. Unless otherwise noted, each message is received by at most one
-//! consumer, i.e., messages are not cloned.
+//! consumer, i.e., messages are not cloned. The `broadcast` channels deliver every
+//! message to every consumer.
 
 pub mod unbounded;
 pub mod bounded_fast;
+pub mod broadcast;
@@ -24,6 +24,12 @@ pub struct Packet<'a, T: Sendable+'a> {
     // Do we still have a sender?
     have_sender: AtomicBool,
 
+    // The token of the primary receiver or `0` if there is none. While there is a primary
+    // receiver, the other receivers don't receive any messages.
+    primary: AtomicUsize,
+    // The token we give to the next receiver.
+    next_token: AtomicUsize,
+
     // Number of sleeping receivers.
     num_sleeping: AtomicUsize,
     // Mutex that protects the variable above.
@@ -68,6 +74,9 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             num_receivers: AtomicUsize::new(1),
             have_sender: AtomicBool::new(true),
 
+            primary: AtomicUsize::new(0),
+            next_token: AtomicUsize::new(1),
+
             num_sleeping: AtomicUsize::new(0),
             sleeping_mutex: Mutex::new(()),
             sleeping_condvar: Condvar::new(),
@@ -83,27 +92,73 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.wait_queue.lock().unwrap().set_id(id);
     }
 
+    /// Returns a new token that identifies a receiver.
+    pub fn new_token(&self) -> usize {
+        self.next_token.fetch_add(1, SeqCst)
+    }
+
     /// Call this when a receiver gets cloned.
     pub fn add_receiver(&self) {
         self.num_receivers.fetch_add(1, SeqCst);
     }
 
+    /// Call this when a weak receiver is upgraded. Returns `false` and does nothing if all
+    /// receivers have already disconnected.
+    pub fn try_add_receiver(&self) -> bool {
+        let mut num = self.num_receivers.load(SeqCst);
+        while num > 0 {
+            let old = self.num_receivers.compare_and_swap(num, num + 1, SeqCst);
+            if old == num {
+                return true;
+            }
+            num = old;
+        }
+        false
+    }
+
     /// Call this when a receiver gets dropped.
-    pub fn remove_receiver(&self) {
+    pub fn remove_receiver(&self, token: usize) {
         self.num_receivers.fetch_sub(1, SeqCst);
+        if self.primary.compare_and_swap(token, 0, SeqCst) == token {
+            // The other receivers might be waiting for the primary receiver to go away.
+            self.notify_all_sleeping(false);
+        }
     }
 
     /// Call this when the sender gets dropped.
     pub fn remove_sender(&self) {
         self.have_sender.store(false, SeqCst);
+        self.notify_all_sleeping(false);
+        self.notify_wait_queue();
+    }
+
+    /// Makes the receiver with token `token` the primary receiver.
+    pub fn set_primary(&self, token: usize) {
+        self.primary.store(token, SeqCst);
+        self.notify_all_sleeping(false);
+    }
+
+    /// Returns whether the receiver with token `token` has to leave the messages to the
+    /// primary receiver.
+    fn is_deferred(&self, token: usize) -> bool {
+        let primary = self.primary.load(SeqCst);
+        primary != 0 && primary != token
+    }
+
+    /// Wakes up all sleeping receivers.
+    fn notify_all_sleeping(&self, have_lock: bool) {
         if self.num_sleeping.load(SeqCst) > 0 {
-            let _guard = self.sleeping_mutex.lock().unwrap();
-            self.sleeping_condvar.notify_all();
+            if have_lock {
+                self.sleeping_condvar.notify_all();
+            } else {
+                let _guard = self.sleeping_mutex.lock().unwrap();
+                self.sleeping_condvar.notify_all();
+            }
         }
-        self.notify_wait_queue();
     }
 
-    fn notify_wait_queue(&self) {
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
         if self.wait_queue_used.load(SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -113,6 +168,11 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
     }
 
     pub fn send(&self, val: T) -> Result<(), (T, Error)> {
+        self.send_counting(val).map(|_| ())
+    }
+
+    /// Sends `val` and returns the number of queued messages right after it was added.
+    pub fn send_counting(&self, val: T) -> Result<usize, (T, Error)> {
         // Don't even try to send anything if all receivers are dead.
         if self.num_receivers.load(SeqCst) == 0 {
             return Err((val, Error::Disconnected));
@@ -124,11 +184,58 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         let write_end = unsafe { &mut *self.write_end.get() };
         write_end.val = Some(val);
         write_end.next.store(new_end, SeqCst);
-        self.num_queued.fetch_add(1, SeqCst); // Maybe we should move this line around a
-                                              // bit?
+        // Maybe we should move this line around a bit?
+        let num_queued = self.num_queued.fetch_add(1, SeqCst) + 1;
         self.write_end.set(new_end);
 
-        if self.num_sleeping.load(SeqCst) > 0 {
+        if self.primary.load(SeqCst) != 0 {
+            // We don't know if `notify_one` would wake up the primary receiver.
+            self.notify_all_sleeping(false);
+        } else if self.num_sleeping.load(SeqCst) > 0 {
+            let _guard = self.sleeping_mutex.lock().unwrap();
+            self.sleeping_condvar.notify_one();
+        }
+
+        self.notify_wait_queue();
+
+        Ok(num_queued)
+    }
+
+    /// Sends all messages in `vals`. The messages become visible to the receivers at the
+    /// same time.
+    pub fn send_all(&self, vals: Vec<T>) -> Result<(), (Vec<T>, Error)> {
+        if self.num_receivers.load(SeqCst) == 0 {
+            return Err((vals, Error::Disconnected));
+        }
+        let num = vals.len();
+        if num == 0 {
+            return Ok(());
+        }
+
+        // The first message goes into the current write end. The others go into a chain
+        // of new nodes that nobody can see until we link it to the write end.
+        let mut vals = vals.into_iter();
+        let first_val = vals.next();
+        let first = Node::new();
+        let mut last = first;
+        for val in vals {
+            let node = Node::new();
+            unsafe {
+                (*last).val = Some(val);
+                (*last).next.store(node, SeqCst);
+            }
+            last = node;
+        }
+
+        let write_end = unsafe { &mut *self.write_end.get() };
+        write_end.val = first_val;
+        write_end.next.store(first, SeqCst);
+        self.num_queued.fetch_add(num, SeqCst);
+        self.write_end.set(last);
+
+        if self.primary.load(SeqCst) != 0 || num > 1 {
+            self.notify_all_sleeping(false);
+        } else if self.num_sleeping.load(SeqCst) > 0 {
             let _guard = self.sleeping_mutex.lock().unwrap();
             self.sleeping_condvar.notify_one();
         }
@@ -138,7 +245,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         Ok(())
     }
 
-    pub fn recv_async(&self) -> Result<T, Error> {
+    pub fn recv_async(&self, token: usize, have_lock: bool) -> Result<T, Error> {
         if self.num_queued.load(SeqCst) == 0 {
             return if !self.have_sender.load(SeqCst) {
                 Err(Error::Disconnected)
@@ -147,6 +254,10 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             };
         }
 
+        if self.is_deferred(token) {
+            return Err(Error::Empty);
+        }
+
         // We have to look at the node in read_end, read next, and then store next in
         // read_end. Unfortunately this is the classic ABA problem. Furthermore, if we
         // just load the value of read_end, then another thread could already deallocate
@@ -161,8 +272,14 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         let next = unsafe { (*read_end).next.load(SeqCst) };
         if !next.is_null() {
             self.read_end.store(next, SeqCst);
-            self.num_queued.fetch_sub(1, SeqCst);
+            let num_queued = self.num_queued.fetch_sub(1, SeqCst);
             let mut node = unsafe { mem::transmute::<_, Box<Node<T>>>(read_end) };
+            if num_queued == 1 && !self.have_sender.load(SeqCst)
+                    && self.primary.load(SeqCst) != 0 {
+                // The deferred receivers have to find out that the channel is
+                // disconnected.
+                self.notify_all_sleeping(have_lock);
+            }
             Ok(node.val.take().unwrap())
         } else {
             self.read_end.store(read_end, SeqCst);
@@ -170,8 +287,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
-    pub fn recv_sync(&self) -> Result<T, Error> {
-        match self.recv_async() {
+    pub fn recv_sync(&self, token: usize) -> Result<T, Error> {
+        match self.recv_async(token, false) {
             v @ Ok(..) => return v,
             Err(Error::Empty) => { },
             e => return e,
@@ -181,7 +298,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         let mut guard = self.sleeping_mutex.lock().unwrap();
         self.num_sleeping.fetch_add(1, SeqCst);
         loop {
-            match self.recv_async() {
+            match self.recv_async(token, true) {
                 v @ Ok(..) => { rv = v; break; }
                 Err(Error::Empty) => { },
                 e => { rv = e; break; }
@@ -191,6 +308,25 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.num_sleeping.fetch_sub(1, SeqCst);
         rv
     }
+
+    /// Receives all messages that are currently in the channel regardless of the primary
+    /// receiver.
+    pub fn drain(&self) -> Vec<T> {
+        let token = self.primary.load(SeqCst);
+        let mut vals = vec!();
+        while let Ok(val) = self.recv_async(token, false) {
+            vals.push(val);
+        }
+        vals
+    }
+
+    pub fn sender_count(&self) -> usize {
+        self.have_sender.load(SeqCst) as usize
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.num_receivers.load(SeqCst)
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
@@ -198,7 +334,7 @@ unsafe impl<'a, T: Sendable+'a> Sync for Packet<'a, T> { }
 
 impl<'a, T: Sendable+'a> Drop for Packet<'a, T> {
     fn drop(&mut self) {
-        while self.recv_async().is_ok() { }
+        self.drain();
         unsafe { ptr::read(self.read_end.load(SeqCst)); }
     }
 }
@@ -208,6 +344,10 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         !self.have_sender.load(SeqCst) || self.num_queued.load(SeqCst) > 0
     }
 
+    fn is_disconnected(&self) -> bool {
+        !self.have_sender.load(SeqCst) && self.num_queued.load(SeqCst) == 0
+    }
+
     fn register(&self, load: Payload<'a>) {
         let mut wait_queue = self.wait_queue.lock().unwrap();
         if wait_queue.add(load) > 0 {
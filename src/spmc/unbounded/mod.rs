@@ -2,8 +2,10 @@
 //!
 //! See the unbounded SPSC documentation.
 
-use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
+use std::{fmt};
+
+use arc::{Arc, ArcTrait, Weak};
+use select::{Selectable, RecvSelectable, _Selectable};
 use {Error, Sendable};
 
 mod imp;
@@ -13,7 +15,21 @@ mod imp;
 pub fn new<'a, T: Sendable+'a>() -> (Producer<'a, T>, Consumer<'a, T>) {
     let packet = Arc::new(imp::Packet::new());
     packet.set_id(packet.unique_id());
-    (Producer { data: packet.clone() }, Consumer { data: packet })
+    let token = packet.new_token();
+    (Producer { data: packet.clone() }, Consumer { data: packet, token: token })
+}
+
+/// Creates a new unbounded SPMC channel with `n` consumers.
+///
+/// If `n` is `0`, the channel is disconnected from the start.
+pub fn new_fan_out<'a, T: Sendable+'a>(n: usize)
+                                       -> (Producer<'a, T>, Vec<Consumer<'a, T>>) {
+    let (send, recv) = new();
+    let mut receivers: Vec<_> = (1..n).map(|_| recv.clone()).collect();
+    if n > 0 {
+        receivers.push(recv);
+    }
+    (send, receivers)
 }
 
 /// The producing end of an unbounded SPMC channel.
@@ -30,6 +46,47 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     pub fn send(&self, val: T) -> Result<(), (T, Error)> {
         self.data.send(val)
     }
+
+    /// Appends a message to the channel and returns the number of messages in the
+    /// channel right after the message was appended.
+    ///
+    /// This can be used to slow down the producer if the consumers can't keep up. The
+    /// count is an upper bound since the consumers might have received messages in the
+    /// meantime.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All receivers have disconnected.
+    pub fn send_counting(&self, val: T) -> Result<usize, (T, Error)> {
+        self.data.send_counting(val)
+    }
+
+    /// Appends all messages in `vals` to the channel.
+    ///
+    /// Each message is still received by exactly one consumer, but the messages are
+    /// appended at once and the sleeping consumers are woken up only once. Either all
+    /// messages are sent or none of them.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - All receivers have disconnected. All messages are returned.
+    pub fn send_all(&self, vals: Vec<T>) -> Result<(), (Vec<T>, Error)> {
+        self.data.send_all(vals)
+    }
+
+    /// Returns the number of producers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn sender_count(&self) -> usize {
+        self.data.sender_count()
+    }
+
+    /// Returns the number of consumers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn receiver_count(&self) -> usize {
+        self.data.receiver_count()
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
@@ -43,6 +100,8 @@ unsafe impl<'a, T: Sendable+'a> Send for Producer<'a, T> { }
 /// The receiving end of an unbounded SPMC channel.
 pub struct Consumer<'a, T: Sendable+'a> {
     data: Arc<imp::Packet<'a, T>>,
+    // Identifies this receiver among its clones.
+    token: usize,
 }
 
 impl<'a, T: Sendable+'a> Consumer<'a, T> {
@@ -52,7 +111,7 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     ///
     /// - `Disconnected` - The channel is empty and the sender has disconnected.
     pub fn recv_sync(&self) -> Result<T, Error> {
-        self.data.recv_sync()
+        self.data.recv_sync(self.token)
     }
 
     /// Receives a message from the channel. Does not block if the channel is empty.
@@ -62,25 +121,113 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     /// - `Disconnected` - The channel is empty and the sender has disconnected.
     /// - `Empty` - The channel is empty.
     pub fn recv_async(&self) -> Result<T, Error> {
-        self.data.recv_async()
+        self.data.recv_async(self.token, false)
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Makes this receiver the primary receiver of the channel.
+    ///
+    /// While the primary receiver is alive, all other receivers behave as if the channel
+    /// were empty. Once the primary receiver is dropped, the other receivers take over
+    /// and receive the messages that are still in the channel. Calling this on another
+    /// receiver makes that receiver the primary receiver instead.
+    ///
+    /// Note that the channel is still reported as ready to `Select` objects that only
+    /// contain non-primary receivers.
+    pub fn set_primary(&self) {
+        self.data.set_primary(self.token);
+    }
+
+    /// Returns the number of producers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn sender_count(&self) -> usize {
+        self.data.sender_count()
+    }
+
+    /// Returns the number of consumers of this channel that are still alive.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn receiver_count(&self) -> usize {
+        self.data.receiver_count()
+    }
+
+    /// Returns whether the sender has disconnected and the channel is empty.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
+
+    /// Creates a weak handle to this channel.
+    ///
+    /// A weak handle doesn't count as a consumer and doesn't keep the channel alive.
+    pub fn downgrade(&self) -> WeakConsumer<'a, T> {
+        WeakConsumer { data: self.data.downgrade() }
+    }
+
+    /// Disconnects this consumer and returns the messages that are still in the channel
+    /// instead of leaving them to the other consumers or dropping them.
+    ///
+    /// This is meant to be called on the last consumer, e.g., to persist unprocessed
+    /// work. If other consumers are still alive, this races with their receive
+    /// operations and they can receive some of the messages first. Messages the sender
+    /// sends while this function runs might not be returned.
+    pub fn into_remaining(self) -> Vec<T> {
+        self.data.drain()
     }
 }
 
 impl<'a, T: Sendable+'a> Clone for Consumer<'a, T> {
     fn clone(&self) -> Consumer<'a, T> {
         self.data.add_receiver();
-        Consumer { data: self.data.clone() }
+        Consumer { data: self.data.clone(), token: self.data.new_token() }
     }
 }
 
 impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
     fn drop(&mut self) {
-        self.data.remove_receiver()
+        self.data.remove_receiver(self.token)
     }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
 
+/// A weak handle to an unbounded SPMC channel.
+pub struct WeakConsumer<'a, T: Sendable+'a> {
+    data: Weak<imp::Packet<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> WeakConsumer<'a, T> {
+    /// Creates a new consumer of the channel.
+    ///
+    /// Returns `None` if all consumers have been dropped in the meantime.
+    pub fn upgrade(&self) -> Option<Consumer<'a, T>> {
+        self.data.upgrade().and_then(|data| {
+            if data.try_add_receiver() {
+                let token = data.new_token();
+                Some(Consumer { data: data, token: token })
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl<'a, T: Sendable+'a> Clone for WeakConsumer<'a, T> {
+    fn clone(&self) -> WeakConsumer<'a, T> {
+        WeakConsumer { data: self.data.clone() }
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for WeakConsumer<'a, T> { }
+
 impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
     fn id(&self) -> usize {
         self.data.unique_id()
@@ -90,3 +237,33 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spmc::unbounded::Producer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spmc::unbounded::Consumer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for WeakConsumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spmc::unbounded::WeakConsumer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
@@ -144,3 +144,166 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn fan_out() {
+    let (send, recvs) = super::new_fan_out(3);
+    assert_eq!(recvs.len(), 3);
+    for i in 0..3 {
+        send.send(i as u8).unwrap();
+    }
+    let mut sum = 0;
+    for recv in &recvs {
+        sum += recv.recv_sync().unwrap();
+    }
+    assert_eq!(sum, 3);
+    drop(recvs);
+    assert_eq!(send.send(1u8).unwrap_err(), (1, Error::Disconnected));
+}
+
+#[test]
+fn fan_out_zero() {
+    let (send, recvs) = super::new_fan_out(0);
+    assert!(recvs.is_empty());
+    assert_eq!(send.send(1u8).unwrap_err(), (1, Error::Disconnected));
+}
+
+#[test]
+fn primary_failover() {
+    let (send, primary) = super::new();
+    let standby = primary.clone();
+    primary.set_primary();
+
+    for i in 0..4u8 {
+        send.send(i).unwrap();
+    }
+    assert_eq!(standby.recv_async().unwrap_err(), Error::Empty);
+    assert_eq!(primary.recv_sync().unwrap(), 0);
+    assert_eq!(primary.recv_sync().unwrap(), 1);
+
+    let guard = thread::scoped(move || {
+        assert_eq!(standby.recv_sync().unwrap(), 2);
+        assert_eq!(standby.recv_sync().unwrap(), 3);
+        assert_eq!(standby.recv_sync().unwrap(), 4);
+        assert_eq!(standby.recv_sync().unwrap_err(), Error::Disconnected);
+    });
+
+    ms_sleep(100);
+    drop(primary);
+    send.send(4).unwrap();
+    drop(send);
+    guard.join();
+}
+
+#[test]
+fn primary_disconnect() {
+    let (send, primary) = super::new();
+    let standby = primary.clone();
+    primary.set_primary();
+    send.send(1u8).unwrap();
+    drop(send);
+
+    let guard = thread::scoped(move || {
+        assert_eq!(standby.recv_sync().unwrap_err(), Error::Disconnected);
+    });
+
+    ms_sleep(100);
+    assert_eq!(primary.recv_sync().unwrap(), 1);
+    guard.join();
+}
+
+#[test]
+fn receiver_count() {
+    let (send, recv) = super::new::<u8>();
+    assert_eq!(recv.receiver_count(), 1);
+    let recv2 = recv.clone();
+    assert_eq!(send.receiver_count(), 2);
+    drop(recv2);
+    assert_eq!(send.receiver_count(), 1);
+    assert_eq!(recv.sender_count(), 1);
+    drop(send);
+    assert_eq!(recv.sender_count(), 0);
+}
+
+#[test]
+fn is_disconnected() {
+    let (send, recv) = super::new();
+    let recv2 = recv.clone();
+    send.send(1u8).unwrap();
+    drop(send);
+    assert!(!recv.is_disconnected());
+    assert_eq!(recv2.recv_async().unwrap(), 1);
+    assert!(recv.is_disconnected());
+    assert!(recv2.is_disconnected());
+}
+
+#[test]
+fn send_counting() {
+    let (send, recv) = super::new();
+    assert_eq!(send.send_counting(1u8).unwrap(), 1);
+    assert_eq!(send.send_counting(2).unwrap(), 2);
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(send.send_counting(3).unwrap(), 2);
+    drop(recv);
+    assert_eq!(send.send_counting(4).unwrap_err(), (4, Error::Disconnected));
+}
+
+#[test]
+fn send_all() {
+    let (send, recv) = super::new();
+    let received = Arc::new(AtomicUsize::new(0));
+    let threads: Vec<_> = (0..2).map(|_| {
+        let recv = recv.clone();
+        let received = received.clone();
+        thread::spawn(move || {
+            while let Ok(n) = recv.recv_sync() {
+                received.fetch_add(n, SeqCst);
+            }
+        })
+    }).collect();
+
+    ms_sleep(100);
+    send.send_all(vec!()).unwrap();
+    send.send_all((1..101).collect()).unwrap();
+    send.send(1000usize).unwrap();
+    drop(send);
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(received.load(SeqCst), 5050 + 1000);
+
+    drop(recv);
+    let (send, recv) = super::new();
+    drop(recv);
+    assert_eq!(send.send_all(vec!(1u8, 2)).unwrap_err(),
+               (vec!(1, 2), Error::Disconnected));
+}
+
+#[test]
+fn weak_consumer() {
+    let (send, recv) = super::new();
+    let weak = recv.downgrade();
+    assert_eq!(recv.receiver_count(), 1);
+    let recv2 = weak.upgrade().unwrap();
+    assert_eq!(recv.receiver_count(), 2);
+    send.send(1u8).unwrap();
+    assert_eq!(recv2.recv_async().unwrap(), 1);
+    drop(recv);
+    drop(recv2);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(send.send(2).unwrap_err(), (2, Error::Disconnected));
+    drop(send);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn into_remaining() {
+    let (send, recv) = super::new();
+    for i in 0..5u8 {
+        send.send(i).unwrap();
+    }
+    assert_eq!(recv.recv_async().unwrap(), 0);
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.into_remaining(), vec!(2, 3, 4));
+    assert_eq!(send.send(5).unwrap_err(), (5, Error::Disconnected));
+}
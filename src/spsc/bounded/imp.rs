@@ -1,16 +1,26 @@
 //! Implementation of the bounded SPSC channel.
 
 use std::{ptr, mem};
-use std::sync::atomic::{AtomicUsize, AtomicBool};
-use std::sync::atomic::Ordering::{SeqCst};
+use std::sync::atomic::{self, AtomicUsize, AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst, Acquire, Release};
 use std::sync::{Mutex, Condvar};
 use alloc::heap::{allocate, deallocate};
 use std::cell::{Cell};
+use std::time::{Duration};
 
+use arc::{Arc};
 use select::{_Selectable, WaitQueue, Payload};
 use alloc::{oom};
 use {Error, Sendable};
 
+thread_local!(static THREAD_TOKEN: u8 = 0);
+
+/// Returns a value that is distinct for all threads that are alive at the same time. A
+/// thread started after another one has exited can get the same value.
+fn thread_token() -> usize {
+    THREAD_TOKEN.with(|t| t as *const u8 as usize)
+}
+
 pub struct Packet<'a, T: Sendable+'a> {
     // Id of the channel. Address of the arc::Inner that contains us.
     id: Cell<usize>,
@@ -20,10 +30,24 @@ pub struct Packet<'a, T: Sendable+'a> {
     // One less than the capacity. Note that the capacity is a power of two.
     cap_mask: usize,
 
+    // `read_pos` and `write_pos` are stored with `Release` and loaded with `Acquire` so
+    // that the slot contents are visible to whoever sees the new position. That's not
+    // enough for the sleeping and wait queue handshakes where one side stores a position
+    // and then loads a flag while the other side stores the flag and then loads the
+    // position. Both sides of those need a `SeqCst` fence between the store and the load.
+    //
     // The position in the buffer (modulo capacity) where we read the next message from
     read_pos:  AtomicUsize,
     // The position in the buffer (modulo capacity) where we write the next message to
     write_pos: AtomicUsize,
+    // The next position that hasn't been claimed for reading. Only the slots before
+    // `read_pos` can be reused by the producer, but both the receiver and `abort` claim
+    // slots by moving this forward with a CAS so that they never read the same slot.
+    // Only maintained if `abortable` is set.
+    next_read: AtomicUsize,
+    // Has the receiver claimed the slot at `read_pos` without releasing it yet? Only
+    // accessed by the receiver and only set if `abortable` is set.
+    front_claimed: Cell<bool>,
 
     // Is one of the endpoints sleeping?
     have_sleeping: AtomicBool,
@@ -37,9 +61,26 @@ pub struct Packet<'a, T: Sendable+'a> {
     // Has the receiver been dropped?
     receiver_disconnected: AtomicBool,
 
+    // Number of messages dropped by `send_lossy`.
+    dropped: AtomicUsize,
+
+    // Can the producer take the unreceived messages back with `abort`? Otherwise the
+    // receiver doesn't have to claim slots with a CAS.
+    abortable: bool,
+
+    // Does `send_sync` check whether it would wait for its own thread?
+    detect_deadlock: bool,
+    // `thread_token` of the thread that last tried to receive. Only maintained if
+    // `detect_deadlock` is set.
+    receiver_thread: AtomicUsize,
+
     // Is someone selecting on this channel?
     wait_queue_used: AtomicBool,
     wait_queue: Mutex<WaitQueue<'a>>,
+
+    // Is someone selecting on the send side of this channel?
+    send_wait_queue_used: AtomicBool,
+    send_wait_queue: Mutex<WaitQueue<'a>>,
 }
 
 impl<'a, T: Sendable+'a> Packet<'a, T> {
@@ -65,6 +106,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
 
             read_pos:  AtomicUsize::new(0),
             write_pos: AtomicUsize::new(0),
+            next_read: AtomicUsize::new(0),
+            front_claimed: Cell::new(false),
 
             have_sleeping: AtomicBool::new(false),
             sleeping_mutex: Mutex::new(()),
@@ -73,8 +116,64 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             sender_disconnected: AtomicBool::new(false),
             receiver_disconnected: AtomicBool::new(false),
 
+            dropped: AtomicUsize::new(0),
+
+            abortable: false,
+
+            detect_deadlock: false,
+            receiver_thread: AtomicUsize::new(0),
+
             wait_queue_used: AtomicBool::new(false),
             wait_queue: Mutex::new(WaitQueue::new()),
+
+            send_wait_queue_used: AtomicBool::new(false),
+            send_wait_queue: Mutex::new(WaitQueue::new()),
+        }
+    }
+
+    /// Creates a packet whose `send_sync` reports a `Deadlock` instead of blocking if
+    /// the calling thread is the one that last tried to receive.
+    pub fn with_deadlock_detection(buf_size: usize) -> Packet<'a, T> {
+        let mut packet = Packet::new(buf_size);
+        packet.detect_deadlock = true;
+        packet
+    }
+
+    /// Creates a packet that supports `abort`.
+    pub fn with_abort(buf_size: usize) -> Packet<'a, T> {
+        let mut packet = Packet::new(buf_size);
+        packet.abortable = true;
+        packet
+    }
+
+    /// Creates a packet whose positions start at `pos` instead of `0`.
+    #[cfg(test)]
+    pub fn new_at(buf_size: usize, pos: usize) -> Packet<'a, T> {
+        let packet = Packet::new(buf_size);
+        packet.read_pos.store(pos, SeqCst);
+        packet.write_pos.store(pos, SeqCst);
+        packet.next_read.store(pos, SeqCst);
+        packet
+    }
+
+    /// Writes the messages produced by `iter` into the buffer. This has to be called
+    /// before the packet is shared with another thread.
+    ///
+    /// ### Panic
+    ///
+    /// Panics if `iter` produces more messages than fit into the buffer. The messages
+    /// written so far are dropped with the packet.
+    pub fn fill<I: Iterator<Item=T>>(&self, iter: I) {
+        let (mut write_pos, read_pos) = self.get_pos();
+        for val in iter {
+            if self.occupancy(write_pos, read_pos) == self.cap_mask + 1 {
+                panic!("iterator exceeds the capacity of the channel");
+            }
+            unsafe {
+                ptr::write(self.buf.offset((write_pos & self.cap_mask) as isize), val);
+            }
+            write_pos = write_pos.wrapping_add(1);
+            self.write_pos.store(write_pos, Release);
         }
     }
 
@@ -84,10 +183,17 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.wait_queue.lock().unwrap().set_id(id);
     }
 
+    /// This has to be called before the send side is used with `Select`. `id` is the id
+    /// of the `Writable` that contains the sender's reference to this packet.
+    pub fn set_send_id(&self, id: usize) {
+        self.send_wait_queue.lock().unwrap().set_id(id);
+    }
+
     /// Wake a sleeping thread if it exists. have_lock is so that we don't deadlock when
     /// we call this function inside the sleep-loop.
     fn notify_sleeping(&self, have_lock: bool) {
         // See the docs in send_sync
+        atomic::fence(SeqCst);
         if self.have_sleeping.load(SeqCst) {
             if have_lock {
                 self.sleeping_condvar.notify_one();
@@ -99,7 +205,15 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
     }
 
     fn get_pos(&self) -> (usize, usize) {
-        (self.write_pos.load(SeqCst), self.read_pos.load(SeqCst))
+        (self.write_pos.load(Acquire), self.read_pos.load(Acquire))
+    }
+
+    /// Returns the number of slots between `read_pos` and `write_pos`. The positions
+    /// wrap around on overflow.
+    fn occupancy(&self, write_pos: usize, read_pos: usize) -> usize {
+        let n = write_pos.wrapping_sub(read_pos);
+        debug_assert!(n <= self.cap_mask + 1, "occupancy exceeds capacity");
+        n
     }
 
     /// Call this when the receiver disconnects.
@@ -108,6 +222,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         if !self.sender_disconnected.load(SeqCst) {
             self.notify_sleeping(false);
         }
+        self.notify_send_wait_queue();
     }
 
     /// Call this when the sender disconnects.
@@ -119,7 +234,10 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.notify_wait_queue();
     }
 
-    fn notify_wait_queue(&self) {
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
+        // See the comment on the `read_pos` field.
+        atomic::fence(SeqCst);
         if self.wait_queue_used.load(SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -128,6 +246,18 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
+    /// Notifies everyone selecting on the send side of this channel.
+    pub fn notify_send_wait_queue(&self) {
+        // See the comment on the `read_pos` field.
+        atomic::fence(SeqCst);
+        if self.send_wait_queue_used.load(SeqCst) {
+            let mut wait_queue = self.send_wait_queue.lock().unwrap();
+            if wait_queue.notify() == 0 {
+                self.send_wait_queue_used.store(false, SeqCst);
+            }
+        }
+    }
+
     pub fn send_async(&self, val: T, have_lock: bool) -> Result<(), (T, Error)> {
         // If the other end disconnected then don't even try to store anything new in the
         // channel.
@@ -136,14 +266,14 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
 
         let (write_pos, read_pos) = self.get_pos();
-        if write_pos - read_pos == self.cap_mask + 1 {
+        if self.occupancy(write_pos, read_pos) == self.cap_mask + 1 {
             return Err((val, Error::Full));
         }
 
         unsafe {
             ptr::write(self.buf.offset((write_pos & self.cap_mask) as isize), val);
         }
-        self.write_pos.store(write_pos + 1, SeqCst);
+        self.write_pos.store(write_pos.wrapping_add(1), Release);
 
         self.notify_sleeping(have_lock);
 
@@ -159,12 +289,20 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             Err((v, _)) => v,
         };
 
+        // The buffer is full. If we're also the receiving thread, nobody is going to
+        // make space.
+        if self.detect_deadlock && self.receiver_thread.load(SeqCst) == thread_token() {
+            return Err((val, Error::Deadlock));
+        }
+
         let mut rv = Ok(());
         // We store have_sleeping after acquiring the lock so that another thread sees
         // this has to wait for us to go to sleep before it can acquire the lock and
-        // notify the condvar.
+        // notify the condvar. The fence pairs with the one in notify_sleeping so that
+        // either we see the new position or the other thread sees have_sleeping.
         let mut guard = self.sleeping_mutex.lock().unwrap();
         self.have_sleeping.store(true, SeqCst);
+        atomic::fence(SeqCst);
         loop {
             val = match self.send_async(val, true) {
                 Ok(()) => break,
@@ -177,26 +315,263 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         rv
     }
 
-    pub fn recv_async(&self, have_lock: bool) -> Result<T, Error> {
+    pub fn send_timeout(&self, val: T, dur: Duration) -> Result<(), (T, Error)> {
+        // See the docs in send_sync.
+
+        let mut val = match self.send_async(val, false) {
+            Ok(()) => return Ok(()),
+            e @ Err((_, Error::Disconnected)) => return e,
+            Err((v, _)) => Some(v),
+        };
+
+        let mut rv = None;
+        let guard = self.sleeping_mutex.lock().unwrap();
+        self.have_sleeping.store(true, SeqCst);
+        atomic::fence(SeqCst);
+        let _guard = self.sleeping_condvar.wait_timeout_with(guard, dur, |_| {
+            match self.send_async(val.take().unwrap(), true) {
+                Err((v, Error::Full)) => { val = Some(v); false },
+                r => { rv = Some(r); true },
+            }
+        }).unwrap().0;
+        // We still hold the lock so nobody will try to wake us up after this.
+        self.have_sleeping.store(false, SeqCst);
+        rv.unwrap_or_else(|| Err((val.take().unwrap(), Error::Full)))
+    }
+
+    /// Returns `Ok` if `send_async` would succeed right now.
+    fn sendable(&self) -> Result<(), Error> {
+        if self.receiver_disconnected.load(SeqCst) {
+            return Err(Error::Disconnected);
+        }
         let (write_pos, read_pos) = self.get_pos();
-        if write_pos == read_pos {
-            return if self.sender_disconnected.load(SeqCst) {
-                Err(Error::Disconnected)
-            } else {
-                Err(Error::Empty)
+        if self.occupancy(write_pos, read_pos) == self.cap_mask + 1 {
+            Err(Error::Full)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Blocks until there is space in the buffer. Blocks for at most `dur` if it is not
+    /// `None`.
+    pub fn wait_sendable(&self, dur: Option<Duration>) -> Result<(), Error> {
+        // See the docs in send_sync.
+
+        match self.sendable() {
+            Err(Error::Full) => { },
+            rv => return rv,
+        }
+
+        let mut rv = Err(Error::Full);
+        let guard = self.sleeping_mutex.lock().unwrap();
+        self.have_sleeping.store(true, SeqCst);
+        atomic::fence(SeqCst);
+        let _guard = {
+            let mut check = || match self.sendable() {
+                Err(Error::Full) => false,
+                r => { rv = r; true },
+            };
+            let guard = match dur {
+                Some(dur) => self.sleeping_condvar.wait_timeout_with(guard, dur, |_| {
+                    check()
+                }).unwrap().0,
+                None => {
+                    let mut guard = guard;
+                    while !check() {
+                        guard = self.sleeping_condvar.wait(guard).unwrap();
+                    }
+                    guard
+                },
             };
+            guard
+        };
+        // We still hold the lock so nobody will try to wake us up after this.
+        self.have_sleeping.store(false, SeqCst);
+        rv
+    }
+
+    /// Claims the slot of the next message and returns its position. Calling this
+    /// twice without releasing the slot in between returns the same position.
+    fn claim(&self) -> Result<usize, Error> {
+        if self.detect_deadlock {
+            let token = thread_token();
+            if self.receiver_thread.load(SeqCst) != token {
+                self.receiver_thread.store(token, SeqCst);
+            }
+        }
+
+        if !self.abortable {
+            // Nobody else claims slots so the slot at `read_pos` stays ours until we
+            // release it.
+            let read_pos = self.read_pos.load(Acquire);
+            if self.write_pos.load(Acquire) == read_pos {
+                return if self.sender_disconnected.load(SeqCst) {
+                    Err(Error::Disconnected)
+                } else {
+                    Err(Error::Empty)
+                };
+            }
+            return Ok(read_pos);
+        }
+
+        if self.front_claimed.get() {
+            return Ok(self.read_pos.load(Acquire));
         }
 
+        let mut next_read = self.next_read.load(SeqCst);
+        loop {
+            if self.write_pos.load(Acquire) == next_read {
+                return if self.sender_disconnected.load(SeqCst) {
+                    Err(Error::Disconnected)
+                } else {
+                    Err(Error::Empty)
+                };
+            }
+            let next = next_read.wrapping_add(1);
+            let old = self.next_read.compare_and_swap(next_read, next, SeqCst);
+            if old == next_read {
+                self.front_claimed.set(true);
+                return Ok(next_read);
+            }
+            // The producer is aborting and was faster than us.
+            next_read = old;
+        }
+    }
+
+    /// Hands the slot at `pos` back to the producer.
+    fn release(&self, pos: usize) {
+        self.front_claimed.set(false);
+        self.read_pos.store(pos.wrapping_add(1), Release);
+    }
+
+    pub fn recv_async(&self, have_lock: bool) -> Result<T, Error> {
+        let read_pos = try!(self.claim());
+
         let val = unsafe {
             ptr::read(self.buf.offset((read_pos & self.cap_mask) as isize))
         };
-        self.read_pos.store(read_pos + 1, SeqCst);
+        self.release(read_pos);
 
         self.notify_sleeping(have_lock);
 
+        self.notify_send_wait_queue();
+
         Ok(val)
     }
 
+    /// Returns a pointer to the next message without removing it from the buffer. The
+    /// producer won't touch the slot until `pop_front` has been called.
+    pub fn front(&self) -> Result<*mut T, Error> {
+        let read_pos = try!(self.claim());
+        unsafe { Ok(self.buf.offset((read_pos & self.cap_mask) as isize)) }
+    }
+
+    /// Drops the message returned by `front` and frees its slot. Must only be called
+    /// after a successful call to `front`.
+    pub unsafe fn pop_front(&self) {
+        let read_pos = self.read_pos.load(Acquire);
+        drop(ptr::read(self.buf.offset((read_pos & self.cap_mask) as isize)));
+        self.release(read_pos);
+
+        self.notify_sleeping(false);
+
+        self.notify_send_wait_queue();
+    }
+
+    /// Disconnects the sender and takes all messages that haven't been claimed by the
+    /// receiver out of the buffer. Must only be called by the sender.
+    ///
+    /// ### Panic
+    ///
+    /// Panics if the packet wasn't created with `with_abort`.
+    pub fn abort(&self) -> Vec<T> {
+        assert!(self.abortable, "abort called on a channel that isn't abortable");
+
+        self.sender_disconnected.store(true, SeqCst);
+
+        // We're the sender so nothing can be added while we're doing this.
+        let write_pos = self.write_pos.load(Acquire);
+        let mut next_read = self.next_read.load(SeqCst);
+        let mut vals = Vec::with_capacity(self.occupancy(write_pos, next_read));
+        while next_read != write_pos {
+            let next = next_read.wrapping_add(1);
+            let old = self.next_read.compare_and_swap(next_read, next, SeqCst);
+            if old == next_read {
+                vals.push(unsafe {
+                    ptr::read(self.buf.offset((next_read & self.cap_mask) as isize))
+                });
+                next_read = next;
+            } else {
+                // The receiver claimed the message before we could.
+                next_read = old;
+            }
+        }
+        vals
+    }
+
+    /// Disconnects the receiver and takes all messages that haven't been received yet
+    /// out of the buffer. Must only be called by the receiver.
+    pub fn drain_remaining(&self) -> Vec<T> {
+        // Disconnect first so that the sender stops adding messages. A message that is
+        // being sent concurrently might still end up behind `write_pos` and is dropped
+        // together with the packet.
+        self.disconnect_receiver();
+
+        let mut vals = vec!();
+
+        // The message returned by `front` has already been claimed but it's still ours.
+        if self.front_claimed.get() {
+            let read_pos = self.read_pos.load(Acquire);
+            vals.push(unsafe {
+                ptr::read(self.buf.offset((read_pos & self.cap_mask) as isize))
+            });
+            self.release(read_pos);
+        }
+
+        let write_pos = self.write_pos.load(Acquire);
+        if !self.abortable {
+            let read_pos = self.read_pos.load(Acquire);
+            let n = self.occupancy(write_pos, read_pos);
+            vals.reserve(n);
+            for i in 0..n {
+                let pos = read_pos.wrapping_add(i);
+                vals.push(unsafe {
+                    ptr::read(self.buf.offset((pos & self.cap_mask) as isize))
+                });
+            }
+            // Only now that we've read them can the producer reuse the slots.
+            self.read_pos.store(write_pos, Release);
+            return vals;
+        }
+
+        // Claim all other messages at once. `abort` might be claiming messages
+        // concurrently and might even have claimed messages beyond our `write_pos`. In
+        // that case the distance to `write_pos` wraps around and exceeds the capacity.
+        let cap = self.cap_mask + 1;
+        let behind = |next_read: usize| {
+            let n = write_pos.wrapping_sub(next_read);
+            n > 0 && n <= cap
+        };
+        let mut next_read = self.next_read.load(SeqCst);
+        while behind(next_read) {
+            let old = self.next_read.compare_and_swap(next_read, write_pos, SeqCst);
+            if old == next_read {
+                break;
+            }
+            next_read = old;
+        }
+
+        let n = if behind(next_read) { write_pos.wrapping_sub(next_read) } else { 0 };
+        vals.reserve(n);
+        for i in 0..n {
+            let pos = next_read.wrapping_add(i);
+            vals.push(unsafe {
+                ptr::read(self.buf.offset((pos & self.cap_mask) as isize))
+            });
+        }
+        vals
+    }
+
     pub fn recv_sync(&self) -> Result<T, Error> {
         // See the docs in send_sync.
 
@@ -209,6 +584,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         let rv;
         let mut guard = self.sleeping_mutex.lock().unwrap();
         self.have_sleeping.store(true, SeqCst);
+        atomic::fence(SeqCst);
         loop {
             match self.recv_async(true) {
                 v @ Ok(..) => { rv = v; break; },
@@ -220,6 +596,79 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.have_sleeping.store(false, SeqCst);
         rv
     }
+
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        // See the docs in send_sync.
+
+        match self.recv_async(false) {
+            v @ Ok(..) => return v,
+            Err(Error::Empty) => { },
+            e => return e,
+        }
+
+        let mut rv = None;
+        let guard = self.sleeping_mutex.lock().unwrap();
+        self.have_sleeping.store(true, SeqCst);
+        atomic::fence(SeqCst);
+        let _guard = self.sleeping_condvar.wait_timeout_with(guard, dur, |_| {
+            match self.recv_async(true) {
+                Err(Error::Empty) => false,
+                v => { rv = Some(v); true },
+            }
+        }).unwrap().0;
+        // We still hold the lock so nobody will try to wake us up after this.
+        self.have_sleeping.store(false, SeqCst);
+        rv.unwrap_or(Err(Error::Empty))
+    }
+
+    pub fn is_drained(&self) -> bool {
+        if !self.sender_disconnected.load(SeqCst) {
+            return false;
+        }
+        let (write_pos, read_pos) = self.get_pos();
+        write_pos == read_pos
+    }
+
+    /// Returns whether one of the endpoints is sleeping. Since the calling endpoint can't
+    /// be sleeping, this is the other endpoint.
+    pub fn has_sleeping(&self) -> bool {
+        self.have_sleeping.load(SeqCst)
+    }
+
+    pub fn send_lossy(&self, val: T) -> bool {
+        match self.send_async(val, false) {
+            Ok(()) => true,
+            Err((_, Error::Full)) => {
+                self.dropped.fetch_add(1, SeqCst);
+                false
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.load(SeqCst)
+    }
+
+    pub fn len(&self) -> usize {
+        let (write_pos, read_pos) = self.get_pos();
+        self.occupancy(write_pos, read_pos)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap_mask + 1
+    }
+
+    /// Returns whether `send_async` would not return `Full`.
+    fn writable(&self) -> bool {
+        if self.receiver_disconnected.load(SeqCst) {
+            return true;
+        }
+        // See the comment on the `read_pos` field.
+        atomic::fence(SeqCst);
+        let (write_pos, read_pos) = self.get_pos();
+        self.occupancy(write_pos, read_pos) < self.cap_mask + 1
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
@@ -228,10 +677,19 @@ unsafe impl<'a, T: Sendable+'a> Sync for Packet<'a, T> { }
 impl<'a, T: Sendable+'a> Drop for Packet<'a, T> {
     fn drop(&mut self) {
         let (write_pos, read_pos) = self.get_pos();
+        let next_read = if self.abortable {
+            self.next_read.load(SeqCst)
+        } else {
+            read_pos
+        };
 
         unsafe {
-            for i in (0..write_pos-read_pos) {
-                ptr::read(self.buf.offset(((read_pos + i) & self.cap_mask) as isize));
+            if self.front_claimed.get() {
+                ptr::read(self.buf.offset((read_pos & self.cap_mask) as isize));
+            }
+            for i in (0..self.occupancy(write_pos, next_read)) {
+                let pos = next_read.wrapping_add(i);
+                ptr::read(self.buf.offset((pos & self.cap_mask) as isize));
             }
 
             if mem::size_of::<T>() > 0 {
@@ -248,10 +706,16 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         if self.sender_disconnected.load(SeqCst) {
             return true;
         }
+        // See the comment on the `read_pos` field.
+        atomic::fence(SeqCst);
         let (write_pos, read_pos) = self.get_pos();
         write_pos != read_pos
     }
 
+    fn is_disconnected(&self) -> bool {
+        self.is_drained()
+    }
+
     fn register(&self, load: Payload<'a>) {
         let mut wait_queue = self.wait_queue.lock().unwrap();
         if wait_queue.add(load) > 0 {
@@ -266,3 +730,36 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         }
     }
 }
+
+/// The send side of the channel. It has its own id and wait queue so that `Select` can
+/// wait for the channel to have space independently of waiting for messages.
+pub struct Writable<'a, T: Sendable+'a> {
+    pub packet: Arc<Packet<'a, T>>,
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Writable<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Writable<'a, T> { }
+
+unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Writable<'a, T> {
+    fn ready(&self) -> bool {
+        self.packet.writable()
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.packet.receiver_disconnected.load(SeqCst)
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        let mut wait_queue = self.packet.send_wait_queue.lock().unwrap();
+        if wait_queue.add(load) > 0 {
+            self.packet.send_wait_queue_used.store(true, SeqCst);
+        }
+    }
+
+    fn unregister(&self, id: usize) {
+        let mut wait_queue = self.packet.send_wait_queue.lock().unwrap();
+        if wait_queue.remove(id) == 0 {
+            self.packet.send_wait_queue_used.store(false, SeqCst);
+        }
+    }
+}
@@ -1,8 +1,12 @@
 //! A bounded SPSC channel.
 
+use std::ops::{Deref};
+use std::time::{Duration};
+use std::{fmt};
+
 use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
-use {Error, Sendable};
+use select::{Selectable, RecvSelectable, _Selectable};
+use {Error, Sendable, TryRecvResult};
 
 mod imp;
 #[cfg(test)] mod test;
@@ -10,18 +14,114 @@ mod imp;
 
 /// Creates a new bounded SPSC channel.
 ///
+/// The capacity is rounded up to a power of two, so there is always space for at least
+/// one message. Use `spsc::rendezvous` for a channel without a buffer.
+///
 /// ### Panic
 ///
 /// Panics if `next_power_of_two(cap) * sizeof(T) >= isize::MAX`.
 pub fn new<'a, T: Sendable+'a>(cap: usize) -> (Producer<'a, T>, Consumer<'a, T>) {
-    let packet = Arc::new(imp::Packet::new(cap));
+    from_packet(imp::Packet::new(cap))
+}
+
+/// Creates a new bounded SPSC channel whose producer can take back the messages that
+/// haven't been received yet with `abort`.
+///
+/// Supporting `abort` costs an atomic read-modify-write operation on every receive, so
+/// channels created with `new` don't do it.
+///
+/// ### Panic
+///
+/// See `new`.
+pub fn new_abortable<'a, T: Sendable+'a>(cap: usize) -> (Producer<'a, T>,
+                                                         Consumer<'a, T>) {
+    from_packet(imp::Packet::with_abort(cap))
+}
+
+/// Creates a new bounded SPSC channel whose `send_sync` detects a simple deadlock.
+///
+/// If the buffer is full and `send_sync` is called on the thread that last tried to
+/// receive from the channel, it returns `Deadlock` instead of blocking forever. This
+/// catches the common mistake of using both ends of a channel on one thread.
+///
+/// The consumer is considered to be on the thread where it last tried to receive. If it
+/// has been moved to another thread since, `Deadlock` can be reported although the new
+/// thread would eventually make space. The same happens if that thread has exited and
+/// `send_sync` is called on a thread that was started later, because the identifier of
+/// an exited thread can be reused. Tracking the thread costs a little on every receive,
+/// so channels created with `new` don't do it.
+///
+/// ### Panic
+///
+/// See `new`.
+pub fn new_with_deadlock_detection<'a, T: Sendable+'a>(cap: usize) -> (Producer<'a, T>,
+                                                                       Consumer<'a, T>) {
+    from_packet(imp::Packet::with_deadlock_detection(cap))
+}
+
+/// Creates a new bounded SPSC channel that already contains the messages produced by
+/// `iter`.
+///
+/// The capacity is rounded up like in `new`. Unlike `new_with_values`, the sender is
+/// still connected and the channel has the requested capacity no matter how many
+/// messages `iter` produces.
+///
+/// ### Panic
+///
+/// Panics if `iter` produces more messages than fit into the channel or if
+/// `next_power_of_two(cap) * sizeof(T) >= isize::MAX`.
+pub fn from_iter<'a, T, I>(iter: I, cap: usize) -> (Producer<'a, T>, Consumer<'a, T>)
+    where T: Sendable+'a,
+          I: IntoIterator<Item=T>,
+{
+    let packet = imp::Packet::new(cap);
+    packet.fill(iter.into_iter());
+    from_packet(packet)
+}
+
+/// Creates a channel whose read and write positions start at `pos`.
+#[cfg(test)]
+fn new_at<'a, T: Sendable+'a>(cap: usize,
+                              pos: usize) -> (Producer<'a, T>, Consumer<'a, T>) {
+    from_packet(imp::Packet::new_at(cap, pos))
+}
+
+fn from_packet<'a, T: Sendable+'a>(packet: imp::Packet<'a, T>) -> (Producer<'a, T>,
+                                                                   Consumer<'a, T>) {
+    let packet = Arc::new(packet);
     packet.set_id(packet.unique_id());
-    (Producer { data: packet.clone() }, Consumer { data: packet })
+    let writable = Arc::new(imp::Writable { packet: packet.clone() });
+    packet.set_send_id(writable.unique_id());
+    (Producer { data: packet.clone(), writable: writable }, Consumer { data: packet })
+}
+
+/// Creates a bounded SPSC channel whose sender has already disconnected.
+///
+/// This is mostly useful for testing code that consumes messages.
+pub fn new_disconnected<'a, T: Sendable+'a>() -> Consumer<'a, T> {
+    new_with_values(vec!())
+}
+
+/// Creates a bounded SPSC channel that contains the messages `vals` and whose sender has
+/// already disconnected.
+///
+/// This is mostly useful for testing code that consumes messages.
+pub fn new_with_values<'a, T: Sendable+'a>(vals: Vec<T>) -> Consumer<'a, T> {
+    let (send, recv) = new(vals.len());
+    for val in vals {
+        assert!(send.send_async(val).is_ok());
+    }
+    recv
 }
 
 /// The producing half of a bounded SPSC channel.
+///
+/// The producer can be added to a `Select` object. It's ready when a message can be sent
+/// without blocking or the receiver has disconnected. Its id is different from the id of
+/// the consumer.
 pub struct Producer<'a, T: Sendable+'a> {
     data: Arc<imp::Packet<'a, T>>,
+    writable: Arc<imp::Writable<'a, T>>,
 }
 
 impl<'a, T: Sendable+'a> Producer<'a, T> {
@@ -30,6 +130,8 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     /// ### Errors
     ///
     /// - `Disconnected` - The receiver has disconnected.
+    /// - `Deadlock` - The buffer is full and this thread is the one that last tried to
+    ///   receive. Only reported by channels created with `new_with_deadlock_detection`.
     pub fn send_sync(&self, val: T) -> Result<(), (T, Error)> {
         self.data.send_sync(val)
     }
@@ -43,6 +145,72 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
         self.data.send_async(val, false)
     }
+
+    /// Disconnects the producer and returns all messages that have not yet been
+    /// received, in the order in which they were sent.
+    ///
+    /// A message that the consumer is currently receiving, e.g. via `recv_guard`, is not
+    /// returned.
+    ///
+    /// ### Panic
+    ///
+    /// Panics if the channel wasn't created with `new_abortable`.
+    pub fn abort(self) -> Vec<T> {
+        self.data.abort()
+    }
+
+    /// Returns whether the receiver is currently blocked in `recv_sync`.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn has_blocked_receiver(&self) -> bool {
+        self.data.has_sleeping()
+    }
+
+    /// Sends a message over the channel. Drops the message if the buffer is full.
+    ///
+    /// Returns whether the message was sent. Messages that are dropped because the
+    /// buffer is full are counted, see `Consumer::dropped_count`. Messages that are
+    /// dropped because the receiver has disconnected are not counted.
+    pub fn send_lossy(&self, val: T) -> bool {
+        self.data.send_lossy(val)
+    }
+
+    /// Sends a message over the channel. Blocks for at most `dur` if the buffer is full.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected.
+    /// - `Full` - The buffer was still full when the timeout expired.
+    pub fn send_timeout(&self, val: T, dur: Duration) -> Result<(), (T, Error)> {
+        self.data.send_timeout(val, dur)
+    }
+
+    /// Blocks until a message can be sent without blocking. Blocks for at most `dur` if
+    /// it is not `None`. Nothing is sent.
+    ///
+    /// Since there is only one producer, the space stays available until this producer
+    /// uses it.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected.
+    /// - `Full` - The buffer was still full when the timeout expired.
+    pub fn wait_sendable(&self, dur: Option<Duration>) -> Result<(), Error> {
+        self.data.wait_sendable(dur)
+    }
+
+    /// Returns the number of messages in the buffer.
+    ///
+    /// The value is only a snapshot and might be stale by the time the caller acts on
+    /// it.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
@@ -53,6 +221,16 @@ impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
 
 unsafe impl<'a, T: Sendable+'a> Send for Producer<'a, T> { }
 
+impl<'a, T: Sendable+'a> Selectable<'a> for Producer<'a, T> {
+    fn id(&self) -> usize {
+        self.writable.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.writable.as_trait(&*self.writable as &(_Selectable+'a)) }
+    }
+}
+
 /// The consuming half of a bounded SPSC channel.
 pub struct Consumer<'a, T: Sendable+'a> {
     data: Arc<imp::Packet<'a, T>>,
@@ -68,6 +246,26 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
         self.data.recv_sync()
     }
 
+    /// Receives a message over this channel. Polls the channel up to `spins` times
+    /// before it blocks like `recv_sync`.
+    ///
+    /// If the sender is almost always about to send, this often avoids putting the
+    /// thread to sleep and waking it up again. If the sender is slow, the polling only
+    /// wastes CPU time.
+    ///
+    /// ### Errors
+    ///
+    /// - `Disconnected` - No message is available and the sender has disconnected.
+    pub fn recv_sync_spin(&self, spins: u32) -> Result<T, Error> {
+        for _ in 0..spins {
+            match self.data.recv_async(false) {
+                Err(Error::Empty) => { },
+                rv => return rv,
+            }
+        }
+        self.data.recv_sync()
+    }
+
     /// Receives a message over this channel. Does not block if no message is available.
     ///
     /// ### Errors
@@ -77,6 +275,169 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn recv_async(&self) -> Result<T, Error> {
         self.data.recv_async(false)
     }
+
+    /// Receives a message from this channel. Does not block if no message is available.
+    ///
+    /// This is like `recv_async` except that the two cases in which no message is
+    /// available are reported as distinct variants of `TryRecvResult`.
+    pub fn try_recv(&self) -> TryRecvResult<T> {
+        match self.data.recv_async(false) {
+            Ok(v) => TryRecvResult::Ok(v),
+            Err(Error::Empty) => TryRecvResult::Empty,
+            Err(Error::Disconnected) => TryRecvResult::Disconnected,
+            Err(e) => unreachable!("recv_async returned {:?}", e),
+        }
+    }
+
+    /// Borrows the next message from the buffer without moving it out of the channel.
+    /// The slot of the message is freed when the guard is dropped.
+    ///
+    /// Note that the producer cannot reuse the slot while the guard is alive. In
+    /// particular, if the capacity of the channel is one, the producer will block until
+    /// the guard has been dropped.
+    ///
+    /// ### Errors
+    ///
+    /// - `Disconnected` - No message is available and the sender has disconnected.
+    /// - `Empty` - No message is available.
+    pub fn recv_guard<'b>(&'b mut self) -> Result<RecvGuard<'b, 'a, T>, Error> {
+        let val = try!(self.data.front());
+        Ok(RecvGuard { data: &self.data, val: val })
+    }
+
+    /// Returns a reference to the next message without removing it from the channel.
+    /// The next call to `recv_async`, `recv_sync`, etc. returns the same message.
+    ///
+    /// Like with `recv_guard`, the producer cannot reuse the slot of the message until it
+    /// has been received. The consumer is borrowed mutably so that the message can't be
+    /// received while the reference is alive.
+    ///
+    /// ### Errors
+    ///
+    /// - `Disconnected` - No message is available and the sender has disconnected.
+    /// - `Empty` - No message is available.
+    pub fn peek(&mut self) -> Result<&T, Error> {
+        let val = try!(self.data.front());
+        unsafe { Ok(&*val) }
+    }
+
+    /// Receives a message by running `f` on it in place. Does not block.
+    ///
+    /// The message is never moved out of the buffer. It's dropped after `f` returns and
+    /// only then can the producer reuse its slot. If `f` panics, the message stays in the
+    /// channel. Like with `peek`, the consumer is borrowed mutably so that `f` can't
+    /// receive the message itself.
+    ///
+    /// ### Errors
+    ///
+    /// - `Disconnected` - No message is available and the sender has disconnected.
+    /// - `Empty` - No message is available.
+    pub fn recv_with<F, R>(&mut self, f: F) -> Result<R, Error>
+        where F: FnOnce(&T) -> R,
+    {
+        let val = try!(self.data.front());
+        let rv = f(unsafe { &*val });
+        unsafe { self.data.pop_front(); }
+        Ok(rv)
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Disconnects the consumer and returns all messages that have not yet been
+    /// received, in the order in which they were sent.
+    ///
+    /// This is faster than calling `recv_async` until the channel is empty. If the
+    /// producer is still connected, messages it sends while this function runs might not
+    /// be returned.
+    pub fn drain_remaining(self) -> Vec<T> {
+        self.data.drain_remaining()
+    }
+
+    /// Returns whether the sender has disconnected and all messages have been
+    /// received.
+    ///
+    /// If this returns `true`, every further receive operation returns `Disconnected`.
+    pub fn is_drained(&self) -> bool {
+        self.data.is_drained()
+    }
+
+    /// Returns whether the sender is currently blocked in `send_sync`.
+    ///
+    /// The result is only a snapshot and might be outdated by the time it's returned.
+    pub fn has_blocked_sender(&self) -> bool {
+        self.data.has_sleeping()
+    }
+
+    /// Returns the number of messages `Producer::send_lossy` has dropped because the
+    /// buffer was full.
+    pub fn dropped_count(&self) -> usize {
+        self.data.dropped_count()
+    }
+
+    /// Receives a message from the channel. Blocks for at most `dur` if the channel is
+    /// empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The channel is empty and the sender has disconnected.
+    /// - `Empty` - The channel was still empty when the timeout expired.
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        self.data.recv_timeout(dur)
+    }
+
+    /// Returns the number of messages in the buffer.
+    ///
+    /// The value is only a snapshot and might be stale by the time the caller acts on
+    /// it.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns whether the buffer is empty.
+    ///
+    /// The value is only a snapshot and might be stale by the time the caller acts on
+    /// it.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Returns an iterator that receives messages until the sender has disconnected.
+    /// Blocks if the channel is empty.
+    pub fn iter<'b>(&'b self) -> Iter<'b, 'a, T> {
+        Iter { data: self }
+    }
+
+    /// Returns an iterator that receives messages until the channel is empty. Never
+    /// blocks.
+    pub fn try_iter<'b>(&'b self) -> TryIter<'b, 'a, T> {
+        TryIter { data: self }
+    }
+
+    /// Returns whether the sender has disconnected and all messages have been received.
+    ///
+    /// This is the same as `is_drained`. Unlike the readiness reported to `Select`
+    /// objects, this is `false` as long as messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.is_drained()
+    }
+}
+
+impl<'b, 'a, T: Sendable+'a> IntoIterator for &'b Consumer<'a, T> {
+    type Item = T;
+    type IntoIter = Iter<'b, 'a, T>;
+
+    fn into_iter(self) -> Iter<'b, 'a, T> {
+        self.iter()
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
@@ -96,3 +457,81 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+/// A message borrowed from the buffer of a bounded SPSC channel.
+///
+/// The message is dropped and its slot is freed when the guard is dropped.
+pub struct RecvGuard<'b, 'a: 'b, T: Sendable+'a> {
+    data: &'b imp::Packet<'a, T>,
+    val: *mut T,
+}
+
+impl<'b, 'a, T: Sendable+'a> Deref for RecvGuard<'b, 'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.val }
+    }
+}
+
+impl<'b, 'a, T: Sendable+'a> Drop for RecvGuard<'b, 'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.data.pop_front(); }
+    }
+}
+
+/// A blocking iterator over the messages in a bounded SPSC channel.
+///
+/// The iterator ends when the channel is empty and the sender has disconnected.
+pub struct Iter<'b, 'a: 'b, T: Sendable+'a> {
+    data: &'b Consumer<'a, T>,
+}
+
+impl<'b, 'a, T: Sendable+'a> Iterator for Iter<'b, 'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.data.recv_sync().ok()
+    }
+}
+
+/// A non-blocking iterator over the messages in a bounded SPSC channel.
+///
+/// The iterator ends when the channel is empty.
+pub struct TryIter<'b, 'a: 'b, T: Sendable+'a> {
+    data: &'b Consumer<'a, T>,
+}
+
+impl<'b, 'a, T: Sendable+'a> Iterator for TryIter<'b, 'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.data.recv_async().ok()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::bounded::Producer")
+         .field("id", &self.data.unique_id())
+         .field("len", &self.data.len())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::bounded::Consumer")
+         .field("id", &self.data.unique_id())
+         .field("len", &self.data.len())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
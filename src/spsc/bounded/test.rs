@@ -1,7 +1,11 @@
 use std::thread::{self, sleep_ms};
+use std::sync::{Arc};
+use std::sync::atomic::{AtomicUsize};
+use std::sync::atomic::Ordering::{SeqCst};
+use std::time::{Duration};
 
 use select::{Select, Selectable};
-use {Error};
+use {Error, TryRecvResult};
 
 fn ms_sleep(ms: i64) {
     sleep_ms(ms as u32);
@@ -41,6 +45,16 @@ fn send_send() {
     assert_eq!(send.send_async(1u8).unwrap_err(), (1, Error::Full));
 }
 
+#[test]
+fn try_recv() {
+    let (send, recv) = super::new::<u8>(2);
+    assert_eq!(recv.try_recv(), TryRecvResult::Empty);
+    send.send_sync(1).unwrap();
+    assert_eq!(recv.try_recv(), TryRecvResult::Ok(1));
+    drop(send);
+    assert_eq!(recv.try_recv(), TryRecvResult::Disconnected);
+}
+
 #[test]
 fn sleep_send_recv() {
     let (send, recv) = super::new(2);
@@ -53,6 +67,21 @@ fn sleep_send_recv() {
     assert_eq!(recv.recv_sync().unwrap(), 1);
 }
 
+#[test]
+fn recv_sync_spin() {
+    let (send, recv) = super::new(2);
+    send.send_sync(1u8).unwrap();
+    assert_eq!(recv.recv_sync_spin(0).unwrap(), 1);
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send_sync(2).unwrap();
+    });
+
+    assert_eq!(recv.recv_sync_spin(1000).unwrap(), 2);
+    assert_eq!(recv.recv_sync_spin(1000).unwrap_err(), Error::Disconnected);
+}
+
 #[test]
 fn send_sleep_recv() {
     let (send, recv) = super::new(2);
@@ -92,6 +121,113 @@ fn send_5_recv_5() {
     assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
 }
 
+#[test]
+fn recv_guard() {
+    let (send, mut recv) = super::new(1);
+    send.send_sync(1u8).unwrap();
+    {
+        let guard = recv.recv_guard().unwrap();
+        assert_eq!(*guard, 1);
+        assert_eq!(send.send_async(2).unwrap_err(), (2, Error::Full));
+    }
+    send.send_async(2).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 2);
+}
+
+#[test]
+fn recv_guard_empty() {
+    let (send, mut recv) = super::new::<u8>(1);
+    assert_eq!(recv.recv_guard().err(), Some(Error::Empty));
+    drop(send);
+    assert_eq!(recv.recv_guard().err(), Some(Error::Disconnected));
+}
+
+#[test]
+fn recv_guard_drops_value() {
+    struct D(Arc<AtomicUsize>);
+
+    impl Drop for D {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let (send, mut recv) = super::new(2);
+    send.send_sync(D(drops.clone())).unwrap();
+    {
+        let _guard = recv.recv_guard().unwrap();
+        assert_eq!(drops.load(SeqCst), 0);
+    }
+    assert_eq!(drops.load(SeqCst), 1);
+}
+
+#[test]
+fn peek() {
+    let (send, mut recv) = super::new(2);
+    assert_eq!(recv.peek().err(), Some(Error::Empty));
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2u8).unwrap();
+    assert_eq!(*recv.peek().unwrap(), 1);
+    assert_eq!(*recv.peek().unwrap(), 1);
+    assert_eq!(send.send_async(3).unwrap_err(), (3, Error::Full));
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(*recv.peek().unwrap(), 2);
+    drop(send);
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+    assert_eq!(recv.peek().err(), Some(Error::Disconnected));
+}
+
+#[test]
+fn abort() {
+    let (send, recv) = super::new_abortable(4);
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2u8).unwrap();
+    send.send_sync(3u8).unwrap();
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(send.abort(), vec!(2, 3));
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn abort_recv_guard() {
+    let (send, mut recv) = super::new_abortable(4);
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2u8).unwrap();
+    {
+        let guard = recv.recv_guard().unwrap();
+        assert_eq!(send.abort(), vec!(2));
+        assert_eq!(*guard, 1);
+    }
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn abort_concurrent_recv() {
+    const NUM: usize = 1000;
+
+    let (send, recv) = super::new_abortable(NUM);
+    for i in 0..NUM {
+        send.send_sync(i).unwrap();
+    }
+    let thread = thread::scoped(move || {
+        let mut sum = 0;
+        while let Ok(n) = recv.recv_sync() {
+            sum += n;
+        }
+        sum
+    });
+    let sum: usize = send.abort().iter().fold(0, |a, b| a + *b);
+    assert_eq!(sum + thread.join(), (NUM - 1) * NUM / 2);
+}
+
+#[test]
+#[should_panic]
+fn abort_not_abortable() {
+    let (send, _recv) = super::new::<u8>(4);
+    send.abort();
+}
+
 #[test]
 fn select_no_wait() {
     let (send, recv) = super::new(2);
@@ -124,3 +260,403 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn poke_selects() {
+    let (_send, recv) = super::new::<u8>(2);
+    let id = recv.id();
+
+    let select = Select::new();
+    select.add(&recv);
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        recv.poke_selects();
+        ms_sleep(100);
+    });
+
+    assert_eq!(select.wait(&mut [0]), &mut [id][..]);
+    // The channel is still not ready so it's removed from the ready list again.
+    assert_eq!(select.wait_timeout(&mut [0], None), Some(&mut [][..]));
+}
+
+#[test]
+fn is_drained() {
+    let (send, recv) = super::new(2);
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2u8).unwrap();
+    assert!(!recv.is_drained());
+    drop(send);
+    assert!(!recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert!(!recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap(), 2);
+    assert!(recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn has_blocked_receiver() {
+    let (send, recv) = super::new(1);
+    assert!(!send.has_blocked_receiver());
+    thread::spawn(move || {
+        assert_eq!(recv.recv_sync().unwrap(), 1u8);
+    });
+    ms_sleep(100);
+    assert!(send.has_blocked_receiver());
+    send.send_sync(1).unwrap();
+}
+
+#[test]
+fn has_blocked_sender() {
+    let (send, recv) = super::new(1);
+    send.send_sync(1u8).unwrap();
+    assert!(!recv.has_blocked_sender());
+    thread::spawn(move || {
+        send.send_sync(2).unwrap();
+    });
+    ms_sleep(100);
+    assert!(recv.has_blocked_sender());
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+}
+
+#[test]
+fn new_disconnected() {
+    let recv = super::new_disconnected::<u8>();
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn new_with_values() {
+    let recv = super::new_with_values(vec!(1u8, 2, 3));
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+    assert_eq!(recv.recv_sync().unwrap(), 3);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn from_iter() {
+    let (send, recv) = super::from_iter(1..4u8, 4);
+    assert_eq!(recv.len(), 3);
+    send.send_async(4).unwrap();
+    assert_eq!(send.send_async(5).unwrap_err(), (5, Error::Full));
+    for i in 1..5 {
+        assert_eq!(recv.recv_async().unwrap(), i);
+    }
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
+}
+
+#[test]
+#[should_panic]
+fn from_iter_over_capacity() {
+    super::from_iter(0..5u8, 4);
+}
+
+#[test]
+fn send_lossy() {
+    let (send, recv) = super::new(2);
+    for i in 0..5u8 {
+        assert_eq!(send.send_lossy(i), i < 2);
+    }
+    assert_eq!(recv.dropped_count(), 3);
+    assert_eq!(recv.recv_async().unwrap(), 0);
+    assert!(send.send_lossy(5));
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert_eq!(recv.recv_async().unwrap(), 5);
+    assert_eq!(recv.dropped_count(), 3);
+}
+
+#[test]
+fn recv_timeout() {
+    let (send, recv) = super::new(2);
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send_sync(1u8).unwrap();
+    });
+    assert_eq!(recv.recv_timeout(Duration::from_millis(10)).unwrap_err(), Error::Empty);
+    assert_eq!(recv.recv_timeout(Duration::from_millis(1000)).unwrap(), 1);
+    assert_eq!(recv.recv_timeout(Duration::from_millis(1000)).unwrap_err(),
+               Error::Disconnected);
+}
+
+#[test]
+fn send_timeout() {
+    let (send, recv) = super::new(1);
+    send.send_sync(1u8).unwrap();
+    assert_eq!(send.send_timeout(2, Duration::from_millis(10)).unwrap_err(),
+               (2, Error::Full));
+    thread::spawn(move || {
+        ms_sleep(100);
+        assert_eq!(recv.recv_sync().unwrap(), 1);
+        assert_eq!(recv.recv_sync().unwrap(), 2);
+    });
+    send.send_timeout(2, Duration::from_millis(1000)).unwrap();
+}
+
+#[test]
+fn wait_sendable() {
+    let (send, recv) = super::new(1);
+    send.wait_sendable(None).unwrap();
+    send.send_sync(1u8).unwrap();
+    assert_eq!(send.wait_sendable(Some(Duration::from_millis(10))).unwrap_err(),
+               Error::Full);
+    let t = thread::spawn(move || {
+        ms_sleep(100);
+        assert_eq!(recv.recv_sync().unwrap(), 1);
+        ms_sleep(100);
+    });
+    send.wait_sendable(None).unwrap();
+    send.send_async(2).unwrap();
+    t.join().unwrap();
+    assert_eq!(send.wait_sendable(None).unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn len_capacity() {
+    let (send, recv) = super::new(3);
+    assert_eq!(send.capacity(), 4);
+    assert_eq!(recv.capacity(), 4);
+    assert!(recv.is_empty());
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2).unwrap();
+    assert_eq!(send.len(), 2);
+    assert_eq!(recv.len(), 2);
+    assert!(!recv.is_empty());
+    recv.recv_sync().unwrap();
+    assert_eq!(recv.len(), 1);
+}
+
+#[test]
+fn iter() {
+    let (send, recv) = super::new(2);
+    thread::spawn(move || {
+        for i in 0..10u8 {
+            send.send_sync(i).unwrap();
+        }
+    });
+    let mut expected = 0;
+    for val in &recv {
+        assert_eq!(val, expected);
+        expected += 1;
+    }
+    assert_eq!(expected, 10);
+}
+
+#[test]
+fn try_iter() {
+    let (send, recv) = super::new(8);
+    for i in 0..5u8 {
+        send.send_sync(i).unwrap();
+    }
+    let vals: Vec<_> = recv.try_iter().collect();
+    assert_eq!(vals, [0, 1, 2, 3, 4]);
+    assert_eq!(recv.try_iter().count(), 0);
+}
+
+#[test]
+fn select_send() {
+    let (send, recv) = super::new(1);
+    send.send_sync(1u8).unwrap();
+
+    let select = Select::new();
+    select.add(&send);
+
+    let mut buf = [0];
+    assert_eq!(select.wait_timeout(&mut buf, Some(Duration::from_millis(100))), None);
+
+    let _t = thread::scoped(move || {
+        ms_sleep(100);
+        assert_eq!(recv.recv_sync().unwrap(), 1);
+        ms_sleep(100);
+    });
+
+    assert_eq!(select.wait(&mut buf), &mut [send.id()][..]);
+    send.send_async(2).unwrap();
+}
+
+#[test]
+fn select_send_disconnect() {
+    let (send, recv) = super::new(1);
+    send.send_sync(1u8).unwrap();
+
+    let select = Select::new();
+    select.add(&send);
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(recv);
+    });
+
+    let mut buf = [0];
+    assert_eq!(select.wait(&mut buf), &mut [send.id()][..]);
+    assert_eq!(send.send_async(2).unwrap_err().1, Error::Disconnected);
+}
+
+#[test]
+fn select_send_recv() {
+    let (send, recv) = super::new(1);
+    assert!(send.id() != recv.id());
+
+    let select = Select::new();
+    select.add(&send);
+    select.add(&recv);
+
+    let mut buf = [0, 0];
+    assert_eq!(select.wait(&mut buf), &mut [send.id()][..]);
+    send.send_async(1u8).unwrap();
+    assert_eq!(select.wait(&mut buf), &mut [recv.id()][..]);
+}
+
+#[test]
+fn debug() {
+    let (send, recv) = super::new(2);
+    send.send_sync(1u8).unwrap();
+    let expected = format!("spsc::bounded::Consumer {{ id: {}, len: 1, capacity: 2 }}",
+                           recv.id());
+    assert_eq!(format!("{:?}", recv), expected);
+}
+
+#[test]
+fn is_disconnected() {
+    let (send, recv) = super::new(2);
+    send.send_sync(1u8).unwrap();
+    drop(send);
+    assert!(!recv.is_disconnected());
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert!(recv.is_disconnected());
+
+    let (send, recv) = super::new::<u8>(2);
+    assert!(!recv.is_disconnected());
+    drop(send);
+    assert!(recv.is_disconnected());
+}
+
+#[test]
+fn drain_remaining() {
+    let (send, recv) = super::new(4);
+    for i in 0..6 {
+        send.send_sync(i as u8).unwrap();
+        if i < 3 {
+            assert_eq!(recv.recv_async().unwrap(), i);
+        }
+    }
+    drop(send);
+    assert_eq!(recv.drain_remaining(), vec!(3, 4, 5));
+}
+
+#[test]
+fn drain_remaining_connected() {
+    let (send, recv) = super::new(4);
+    send.send_sync(1u8).unwrap();
+    assert_eq!(recv.drain_remaining(), vec!(1));
+    assert_eq!(send.send_async(2).unwrap_err(), (2, Error::Disconnected));
+}
+
+#[test]
+fn drain_remaining_after_peek() {
+    let (send, mut recv) = super::new(4);
+    send.send_sync(1u8).unwrap();
+    send.send_sync(2u8).unwrap();
+    assert_eq!(*recv.peek().unwrap(), 1);
+    drop(send);
+    assert_eq!(recv.drain_remaining(), vec!(1, 2));
+}
+
+#[test]
+fn positions_wrap_around() {
+    let (send, recv) = super::new_at(4, !0 - 5);
+    for i in 0..3 {
+        for j in 0..4 {
+            send.send_async(4 * i + j).unwrap();
+        }
+        assert_eq!(send.send_async(100u8).unwrap_err(), (100, Error::Full));
+        assert_eq!(recv.len(), 4);
+        for j in 0..4 {
+            assert_eq!(recv.recv_async().unwrap(), 4 * i + j);
+        }
+        assert_eq!(recv.len(), 0);
+    }
+    send.send_async(1).unwrap();
+    send.send_async(2).unwrap();
+    assert_eq!(recv.drain_remaining(), vec!(1, 2));
+}
+
+#[test]
+fn recv_with() {
+    let (send, mut recv) = super::new(1);
+    assert_eq!(recv.recv_with(|_: &[u8; 1024]| ()).unwrap_err(), Error::Empty);
+    send.send_sync([1u8; 1024]).unwrap();
+    let sum = recv.recv_with(|val| {
+        // The slot isn't released while we're looking at the message.
+        assert_eq!(send.send_async([2; 1024]).unwrap_err().1, Error::Full);
+        val.iter().fold(0, |sum, &b| sum + b as usize)
+    });
+    assert_eq!(sum.unwrap(), 1024);
+    send.send_async([2; 1024]).unwrap();
+    assert_eq!(recv.recv_async().unwrap()[0], 2);
+    drop(send);
+    assert_eq!(recv.recv_with(|val| val[0]).unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn stress_ordering() {
+    // Run with `--release` to get enough iterations to catch ordering bugs.
+    let n: usize = if cfg!(debug_assertions) { 100_000 } else { 10_000_000 };
+    let (send, recv) = super::new(4);
+
+    thread::spawn(move || {
+        for i in 0..n {
+            // Boxes make reading a slot before its contents are visible likely to crash.
+            let mut val = Box::new(i);
+            if i % 2 == 0 {
+                send.send_sync(val).unwrap();
+                continue;
+            }
+            loop {
+                match send.send_async(val) {
+                    Ok(()) => break,
+                    Err((v, Error::Full)) => val = v,
+                    Err((_, e)) => panic!("{:?}", e),
+                }
+            }
+        }
+    });
+
+    for i in 0..n {
+        let val;
+        if i % 3 == 0 {
+            val = recv.recv_sync().unwrap();
+        } else {
+            loop {
+                match recv.recv_async() {
+                    Ok(v) => { val = v; break; },
+                    Err(Error::Empty) => { },
+                    Err(e) => panic!("{:?}", e),
+                }
+            }
+        }
+        assert_eq!(*val, i);
+    }
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn deadlock_detection() {
+    let (send, recv) = super::new_with_deadlock_detection(1);
+    // Nobody has tried to receive yet.
+    send.send_sync(1u8).unwrap();
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    send.send_sync(2).unwrap();
+    assert_eq!(send.send_sync(3).unwrap_err(), (3, Error::Deadlock));
+
+    // Once another thread receives, blocking is fine again.
+    let t = thread::scoped(move || {
+        assert_eq!(recv.recv_sync().unwrap(), 2);
+        assert_eq!(recv.recv_sync().unwrap(), 3);
+    });
+    ms_sleep(100);
+    send.send_sync(3).unwrap();
+    drop(t);
+}
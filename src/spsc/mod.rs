@@ -5,4 +5,5 @@
 pub mod one_space;
 pub mod bounded;
 pub mod ring_buf;
+pub mod rendezvous;
 pub mod unbounded;
@@ -205,6 +205,21 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
+    /// Makes the packet usable by a new sender. Returns `false` and does nothing if the
+    /// sender hasn't disconnected yet or data is available.
+    ///
+    /// This function must only be called by the Receiver in the parent module. Since the
+    /// sender has disconnected and the receiver is not `Sync`, nobody else can operate on
+    /// the packet at the same time.
+    pub fn reset(&self) -> bool {
+        let flags = self.flags.load(Ordering::SeqCst);
+        if flags & (SENDER_DISCONNECTED | DATA_AVAILABLE) != SENDER_DISCONNECTED {
+            return false;
+        }
+        self.flags.fetch_and(!SENDER_DISCONNECTED, Ordering::SeqCst);
+        true
+    }
+
     /// Disconnect the receiver.
     ///
     /// This function must only be called from the Receiver in the parent module.
@@ -212,6 +227,15 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.flags.fetch_or(RECEIVER_DISCONNECTED, Ordering::SeqCst);
     }
 
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
+        if self.flags.load(Ordering::SeqCst) & WAIT_QUEUE_USED != 0 {
+            if self.wait_queue(|q| q.notify()) == 0 {
+                self.flags.fetch_and(!WAIT_QUEUE_USED, Ordering::SeqCst);
+            }
+        }
+    }
+
     /// Get the wait queue.
     pub fn wait_queue<F, U>(&self, f: F) -> U where F: FnOnce(&mut WaitQueue<'a>) -> U {
         unsafe {
@@ -230,6 +254,11 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         self.flags.load(Ordering::SeqCst) & (DATA_AVAILABLE | SENDER_DISCONNECTED) != 0
     }
 
+    fn is_disconnected(&self) -> bool {
+        let flags = self.flags.load(Ordering::SeqCst);
+        flags & (DATA_AVAILABLE | SENDER_DISCONNECTED) == SENDER_DISCONNECTED
+    }
+
     fn register(&self, load: Payload<'a>) {
         if self.wait_queue(|q| q.add(load)) > 0 {
             self.flags.fetch_or(WAIT_QUEUE_USED, Ordering::SeqCst);
@@ -6,11 +6,14 @@
 //!
 //! Consider the case of an event loop. To request information from the event loop,
 //! another thread might send the event loop a message and the event loop will send the
-//! answer over the channel that was sent together with the request.
+//! answer over the channel that was sent together with the request. `reply_pair` wraps
+//! this idiom in a one-shot API.
+
+use std::{fmt};
 
 use arc::{Arc, ArcTrait};
 use self::imp::{Packet};
-use select::{Selectable, _Selectable};
+use select::{Selectable, RecvSelectable, _Selectable};
 use {Error, Sendable};
 
 mod imp;
@@ -79,6 +82,35 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn can_recv(&self) -> bool {
         self.data.ready()
     }
+
+    /// Creates a new producer for this channel so that it can be used for another
+    /// message without allocating a new channel.
+    ///
+    /// This is only possible after the previous producer has been dropped and its
+    /// message, if any, has been received. Otherwise `None` is returned and the channel
+    /// is not modified. Afterwards the consumer is in the same state as a newly created
+    /// one and keeps its id.
+    pub fn reset(&self) -> Option<Producer<'a, T>> {
+        if self.data.reset() {
+            Some(Producer { data: self.data.clone() })
+        } else {
+            None
+        }
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns whether the sender has disconnected and all messages have been received.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
@@ -98,3 +130,92 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+/// Creates a new one-shot reply channel.
+///
+/// The `ReplySender` is sent together with a request and the answer is sent back with
+/// `ReplySender::respond`.
+pub fn reply_pair<'a, T: Sendable+'a>() -> (ReplySender<'a, T>, ReplyReceiver<'a, T>) {
+    let (send, recv) = new();
+    (ReplySender { data: send }, ReplyReceiver { data: recv })
+}
+
+/// The sending half of a one-shot reply channel.
+pub struct ReplySender<'a, T: Sendable+'a> {
+    data: Producer<'a, T>,
+}
+
+impl<'a, T: Sendable+'a> ReplySender<'a, T> {
+    /// Sends the reply.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected.
+    pub fn respond(self, val: T) -> Result<(), (T, Error)> {
+        self.data.send(val)
+    }
+}
+
+/// The receiving half of a one-shot reply channel.
+pub struct ReplyReceiver<'a, T: Sendable+'a> {
+    data: Consumer<'a, T>,
+}
+
+impl<'a, T: Sendable+'a> ReplyReceiver<'a, T> {
+    /// Waits for the reply.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The sender has been dropped without responding.
+    pub fn wait(self) -> Result<T, Error> {
+        self.data.recv_sync()
+    }
+}
+
+impl<'a, T: Sendable+'a> Selectable<'a> for ReplyReceiver<'a, T> {
+    fn id(&self) -> usize {
+        self.data.id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        self.data.as_selectable()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::one_space::Producer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::one_space::Consumer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for ReplySender<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::one_space::ReplySender")
+         .field("id", &self.data.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for ReplyReceiver<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::one_space::ReplyReceiver")
+         .field("id", &self.data.data.unique_id())
+         .finish()
+    }
+}
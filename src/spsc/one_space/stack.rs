@@ -1,7 +1,7 @@
 //! An SPSC channel with a buffer size of one stored on the stack.
 
-use std::{mem};
 use super::imp::{Packet};
+use select::{_Selectable};
 use {Error, Sendable};
 
 /// Creates a new SPSC one space channel.
@@ -16,21 +16,45 @@ pub struct Slot<'a, T: Sendable+'a> {
 
 impl<'a, T: Sendable+'a> Slot<'a, T> {
     /// Split the slot into a producing and a consuming end.
-    pub fn split(&mut self) -> (&Producer<'a, T>, &Consumer<'a, T>) {
-        unsafe {
-            let prod = mem::transmute_copy(&self);
-            let cons = mem::transmute(self);
-            (prod, cons)
-        }
+    ///
+    /// Both ends borrow the slot, so the slot can't be moved or dropped while they're
+    /// alive. Every call starts with an empty channel, even if the ends of a previous
+    /// call have been dropped or a message has not been received.
+    ///
+    /// The slot can't be moved while the ends are alive:
+    ///
+    /// ```compile_fail
+    /// use comm::spsc::one_space::stack;
+    ///
+    /// let mut slot = stack::new::<u8>();
+    /// let (send, _recv) = slot.split();
+    /// let moved = slot;
+    /// send.send(1).unwrap();
+    /// ```
+    ///
+    /// Nor can it be split again:
+    ///
+    /// ```compile_fail
+    /// use comm::spsc::one_space::stack;
+    ///
+    /// let mut slot = stack::new::<u8>();
+    /// let (send, _recv) = slot.split();
+    /// let (send2, _recv2) = slot.split();
+    /// send.send(1).unwrap();
+    /// ```
+    pub fn split<'s>(&'s mut self) -> (Producer<'s, 'a, T>, Consumer<'s, 'a, T>) {
+        self.data = Packet::new();
+        let data = &self.data;
+        (Producer { data: data }, Consumer { data: data })
     }
 }
 
 /// The producing half of an SPSC one space channel.
-pub struct Producer<'a, T: Sendable+'a> {
-    data: Packet<'a, T>,
+pub struct Producer<'s, 'a: 's, T: Sendable+'a> {
+    data: &'s Packet<'a, T>,
 }
 
-impl<'a, T: Sendable+'a> Producer<'a, T> {
+impl<'s, 'a: 's, T: Sendable+'a> Producer<'s, 'a, T> {
     /// Sends a message over this channel. Doesn't block if the channel is full.
     ///
     /// ### Error
@@ -42,20 +66,18 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     }
 }
 
-unsafe impl<'a, T: Sendable+'a> Send for Producer<'a, T> { }
-
-impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
+impl<'s, 'a: 's, T: Sendable+'a> Drop for Producer<'s, 'a, T> {
     fn drop(&mut self) {
         self.data.sender_disconnect();
     }
 }
 
 /// The consuming half of an SPSC one space channel.
-pub struct Consumer<'a, T: Sendable+'a> {
-    data: Packet<'a, T>,
+pub struct Consumer<'s, 'a: 's, T: Sendable+'a> {
+    data: &'s Packet<'a, T>,
 }
 
-impl<'a, T: Sendable+'a> Consumer<'a, T> {
+impl<'s, 'a: 's, T: Sendable+'a> Consumer<'s, 'a, T> {
     /// Receives a message from this channel. Doesn't block if the channel is empty.
     ///
     /// ### Error
@@ -74,11 +96,17 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn recv_sync(&self) -> Result<T, Error> {
         self.data.recv_sync()
     }
-}
 
-unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
+    /// Returns whether the sender has disconnected and all messages have been received.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
+}
 
-impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
+impl<'s, 'a: 's, T: Sendable+'a> Drop for Consumer<'s, 'a, T> {
     fn drop(&mut self) {
         self.data.recv_disconnect();
     }
@@ -122,3 +122,64 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn reply_pair() {
+    let (requests, event_loop) = ::mpsc::unbounded::new();
+    thread::spawn(move || {
+        while let Ok((val, reply)) = event_loop.recv_sync() {
+            let reply: super::ReplySender<u8> = reply;
+            reply.respond(val + 1).unwrap();
+        }
+    });
+    for i in 0..10u8 {
+        let (reply, answer) = super::reply_pair();
+        requests.send((i, reply)).unwrap();
+        assert_eq!(answer.wait().unwrap(), i + 1);
+    }
+}
+
+#[test]
+fn reply_pair_dropped() {
+    let (reply, answer) = super::reply_pair::<u8>();
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(reply);
+    });
+    assert_eq!(answer.wait().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn reset() {
+    let (send, recv) = super::new();
+    assert!(recv.reset().is_none());
+    send.send(1u8).unwrap();
+    drop(send);
+    assert!(recv.reset().is_none());
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    let send = recv.reset().unwrap();
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
+    send.send(2).unwrap();
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+    drop(send);
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn stack_split() {
+    let mut slot = super::stack::new();
+    {
+        let (send, recv) = slot.split();
+        let _t = thread::scoped(move || {
+            ms_sleep(100);
+            send.send(1u8).unwrap();
+        });
+        assert_eq!(recv.recv_sync().unwrap(), 1);
+        assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+    }
+    {
+        let (send, recv) = slot.split();
+        send.send(2).unwrap();
+        assert_eq!(recv.recv_async().unwrap(), 2);
+    }
+}
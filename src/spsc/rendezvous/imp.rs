@@ -0,0 +1,192 @@
+use std::sync::atomic::{AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst};
+use std::sync::{Mutex, Condvar};
+
+use select::{_Selectable, WaitQueue, Payload};
+use {Error, Sendable};
+
+pub struct Packet<'a, T: Sendable+'a> {
+    inner: Mutex<Inner<T>>,
+    // Condvar both ends are waiting on. There is at most one thread on each side.
+    condvar: Condvar,
+
+    // Is any one selecting on this channel? The wait queue is not part of `inner` because
+    // `ready` acquires the `inner` lock and therefore we must never hold it while we
+    // interact with the wait queue.
+    wait_queue_used: AtomicBool,
+    wait_queue: Mutex<WaitQueue<'a>>,
+}
+
+struct Inner<T> {
+    // The message the sender is currently trying to hand over.
+    offer: Option<T>,
+    // Is the receiver blocked in `recv_sync`?
+    receiver_waiting: bool,
+
+    sender_disconnected: bool,
+    receiver_disconnected: bool,
+}
+
+impl<'a, T: Sendable+'a> Packet<'a, T> {
+    pub fn new() -> Packet<'a, T> {
+        Packet {
+            inner: Mutex::new(Inner {
+                offer: None,
+                receiver_waiting: false,
+                sender_disconnected: false,
+                receiver_disconnected: false,
+            }),
+            condvar: Condvar::new(),
+
+            wait_queue_used: AtomicBool::new(false),
+            wait_queue: Mutex::new(WaitQueue::new()),
+        }
+    }
+
+    /// Call this function before any other.
+    pub fn set_id(&self, id: usize) {
+        self.wait_queue.lock().unwrap().set_id(id);
+    }
+
+    /// Call this function when the producer is dropped.
+    pub fn remove_sender(&self) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.sender_disconnected = true;
+            self.condvar.notify_all();
+        }
+        self.notify_wait_queue();
+    }
+
+    /// Call this function when the consumer is dropped.
+    pub fn remove_receiver(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.receiver_disconnected = true;
+        self.condvar.notify_all();
+    }
+
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
+        if self.wait_queue_used.load(SeqCst) {
+            let mut wait_queue = self.wait_queue.lock().unwrap();
+            if wait_queue.notify() == 0 {
+                self.wait_queue_used.store(false, SeqCst);
+            }
+        }
+    }
+
+    pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.receiver_disconnected {
+                return Err((val, Error::Disconnected));
+            }
+            if !inner.receiver_waiting || inner.offer.is_some() {
+                return Err((val, Error::Full));
+            }
+            // The receiver doesn't leave `recv_sync` before it has taken the message.
+            inner.offer = Some(val);
+            self.condvar.notify_all();
+        }
+        self.notify_wait_queue();
+        Ok(())
+    }
+
+    pub fn send_sync(&self, val: T) -> Result<(), (T, Error)> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            // A message offered by `send_async` might not have been taken yet.
+            while inner.offer.is_some() && !inner.receiver_disconnected {
+                inner = self.condvar.wait(inner).unwrap();
+            }
+            if inner.receiver_disconnected {
+                return Err((val, Error::Disconnected));
+            }
+            inner.offer = Some(val);
+            self.condvar.notify_all();
+        }
+        self.notify_wait_queue();
+
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            if inner.offer.is_none() {
+                return Ok(());
+            }
+            if inner.receiver_disconnected {
+                return Err((inner.offer.take().unwrap(), Error::Disconnected));
+            }
+            inner = self.condvar.wait(inner).unwrap();
+        }
+    }
+
+    pub fn recv_async(&self) -> Result<T, Error> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.offer.take() {
+            Some(val) => {
+                self.condvar.notify_all();
+                Ok(val)
+            },
+            None => {
+                if inner.sender_disconnected {
+                    Err(Error::Disconnected)
+                } else {
+                    Err(Error::Empty)
+                }
+            },
+        }
+    }
+
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.receiver_waiting = true;
+        let rv;
+        loop {
+            if let Some(val) = inner.offer.take() {
+                self.condvar.notify_all();
+                rv = Ok(val);
+                break;
+            }
+            if inner.sender_disconnected {
+                rv = Err(Error::Disconnected);
+                break;
+            }
+            inner = self.condvar.wait(inner).unwrap();
+        }
+        inner.receiver_waiting = false;
+        rv
+    }
+
+    /// Returns whether the receiver is blocked in `recv_sync`.
+    pub fn receiver_waiting(&self) -> bool {
+        self.inner.lock().unwrap().receiver_waiting
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
+unsafe impl<'a, T: Sendable+'a> Sync for Packet<'a, T> { }
+
+unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
+    fn ready(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.offer.is_some() || inner.sender_disconnected
+    }
+
+    fn is_disconnected(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.offer.is_none() && inner.sender_disconnected
+    }
+
+    fn register(&self, load: Payload<'a>) {
+        let mut wait_queue = self.wait_queue.lock().unwrap();
+        if wait_queue.add(load) > 0 {
+            self.wait_queue_used.store(true, SeqCst);
+        }
+    }
+
+    fn unregister(&self, id: usize) {
+        let mut wait_queue = self.wait_queue.lock().unwrap();
+        if wait_queue.remove(id) == 0 {
+            self.wait_queue_used.store(false, SeqCst);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! An SPSC channel without a buffer.
+//!
+//! A message is only transferred when both ends meet: `send_sync` blocks until the
+//! receiver has taken the message and `send_async` only succeeds if the receiver is
+//! blocked in `recv_sync`. Conversely, `recv_async` only succeeds if the sender is
+//! blocked in `send_sync`.
+
+use std::{fmt};
+
+use arc::{Arc, ArcTrait};
+use select::{Selectable, RecvSelectable, _Selectable};
+use {Error, Sendable};
+
+mod imp;
+#[cfg(test)] mod test;
+
+/// Creates a new rendezvous SPSC channel.
+pub fn new<'a, T: Sendable+'a>() -> (Producer<'a, T>, Consumer<'a, T>) {
+    let packet = Arc::new(imp::Packet::new());
+    packet.set_id(packet.unique_id());
+    (Producer { data: packet.clone() }, Consumer { data: packet })
+}
+
+/// The producing half of a rendezvous SPSC channel.
+pub struct Producer<'a, T: Sendable+'a> {
+    data: Arc<imp::Packet<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> Producer<'a, T> {
+    /// Sends a message over the channel. Blocks until the receiver has received the
+    /// message.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected before receiving the message.
+    pub fn send_sync(&self, val: T) -> Result<(), (T, Error)> {
+        self.data.send_sync(val)
+    }
+
+    /// Sends a message over the channel. Does not block.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected.
+    /// - `Full` - The receiver is not blocked in `recv_sync`.
+    pub fn send_async(&self, val: T) -> Result<(), (T, Error)> {
+        self.data.send_async(val)
+    }
+
+    /// Returns whether the receiver is blocked in `recv_sync`, i.e., whether `send_async`
+    /// would succeed. The result can be outdated by the time it is returned.
+    pub fn receiver_waiting(&self) -> bool {
+        self.data.receiver_waiting()
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Producer<'a, T> { }
+
+impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
+    fn drop(&mut self) {
+        self.data.remove_sender();
+    }
+}
+
+/// The consuming half of a rendezvous SPSC channel.
+///
+/// The consumer can be added to a `Select` object. It's ready when the sender is blocked
+/// in `send_sync` or has disconnected.
+pub struct Consumer<'a, T: Sendable+'a> {
+    data: Arc<imp::Packet<'a, T>>,
+}
+
+impl<'a, T: Sendable+'a> Consumer<'a, T> {
+    /// Receives a message from the channel. Blocks until the sender sends a message.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The sender has disconnected.
+    pub fn recv_sync(&self) -> Result<T, Error> {
+        self.data.recv_sync()
+    }
+
+    /// Receives a message from the channel. Does not block.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The sender has disconnected.
+    /// - `Empty` - The sender is not blocked in `send_sync`.
+    pub fn recv_async(&self) -> Result<T, Error> {
+        self.data.recv_async()
+    }
+
+    /// Returns whether the sender has disconnected and all messages have been received.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
+}
+
+unsafe impl<'a, T: Sendable+'a> Send for Consumer<'a, T> { }
+
+impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
+    fn drop(&mut self) {
+        self.data.remove_receiver();
+    }
+}
+
+impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
+    fn id(&self) -> usize {
+        self.data.unique_id()
+    }
+
+    fn as_selectable(&self) -> ArcTrait<_Selectable<'a>+'a> {
+        unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
+    }
+}
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::rendezvous::Producer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::rendezvous::Consumer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
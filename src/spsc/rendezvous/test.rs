@@ -0,0 +1,126 @@
+use std::thread::{self, sleep_ms};
+use std::sync::atomic::{AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst};
+
+use select::{Select, Selectable};
+use {Error};
+
+fn ms_sleep(ms: i64) {
+    sleep_ms(ms as u32);
+}
+
+#[test]
+fn send_async_no_receiver() {
+    let (send, _recv) = super::new();
+    assert_eq!(send.send_async(1u8).unwrap_err(), (1, Error::Full));
+}
+
+#[test]
+fn recv_async_no_sender() {
+    let (_send, recv) = super::new::<u8>();
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
+}
+
+#[test]
+fn drop_send_recv() {
+    let (send, recv) = super::new::<u8>();
+    drop(send);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn drop_recv_send() {
+    let (send, recv) = super::new();
+    drop(recv);
+    assert_eq!(send.send_sync(1u8).unwrap_err(), (1, Error::Disconnected));
+}
+
+#[test]
+fn send_sync_waits_for_recv() {
+    let (send, recv) = super::new();
+    let sent = AtomicBool::new(false);
+
+    let t = thread::scoped(|| {
+        send.send_sync(1u8).unwrap();
+        sent.store(true, SeqCst);
+    });
+
+    ms_sleep(100);
+    assert!(!sent.load(SeqCst));
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    drop(t);
+    assert!(sent.load(SeqCst));
+}
+
+#[test]
+fn send_sync_recv_async() {
+    let (send, recv) = super::new();
+
+    thread::spawn(move || {
+        send.send_sync(1u8).unwrap();
+    });
+
+    ms_sleep(100);
+    assert_eq!(recv.recv_async().unwrap(), 1);
+}
+
+#[test]
+fn recv_sync_send_async() {
+    let (send, recv) = super::new();
+
+    thread::spawn(move || {
+        assert_eq!(recv.recv_sync().unwrap(), 1u8);
+    });
+
+    while !send.receiver_waiting() {
+        ms_sleep(10);
+    }
+    send.send_async(1).unwrap();
+}
+
+#[test]
+fn send_async_send_sync() {
+    let (send, recv) = super::new();
+
+    let t = thread::scoped(move || {
+        let first = recv.recv_sync().unwrap();
+        ms_sleep(100);
+        (first, recv.recv_sync().unwrap())
+    });
+
+    while !send.receiver_waiting() {
+        ms_sleep(10);
+    }
+    send.send_async(1u8).unwrap();
+    send.send_sync(2).unwrap();
+    assert_eq!(t.join(), (1, 2));
+}
+
+#[test]
+fn recv_disconnect_while_sending() {
+    let (send, recv) = super::new();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(recv);
+    });
+
+    assert_eq!(send.send_sync(1u8).unwrap_err(), (1, Error::Disconnected));
+}
+
+#[test]
+fn select_wait() {
+    let (send, recv) = super::new();
+
+    thread::spawn(move || {
+        ms_sleep(100);
+        send.send_sync(1u8).unwrap();
+    });
+
+    let select = Select::new();
+    select.add(&recv);
+
+    let mut buf = [0];
+    assert_eq!(select.wait(&mut buf), &mut [recv.id()][..]);
+    assert_eq!(recv.recv_async().unwrap(), 1);
+}
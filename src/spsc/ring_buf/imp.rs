@@ -1,8 +1,9 @@
-use std::{ptr, mem};
+use std::{ptr, mem, cmp};
 use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering};
 use std::sync::{Mutex, Condvar};
 use alloc::heap::{allocate, deallocate};
 use std::cell::{Cell};
+use std::time::{Duration};
 
 use select::{_Selectable, WaitQueue, Payload};
 use alloc::{oom};
@@ -34,6 +35,9 @@ pub struct Packet<'a, T: Sendable+'a> {
     // Has the receiver disconnected?
     receiver_disconnected: AtomicBool,
 
+    // Number of messages that have been overwritten by `send`.
+    overwrites: AtomicUsize,
+
     // Is anyone selecting on us?
     wait_queue_used: AtomicBool,
     wait_queue: Mutex<WaitQueue<'a>>,
@@ -70,6 +74,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             sender_disconnected: AtomicBool::new(false),
             receiver_disconnected: AtomicBool::new(false),
 
+            overwrites: AtomicUsize::new(0),
+
             wait_queue_used: AtomicBool::new(false),
             wait_queue: Mutex::new(WaitQueue::new()),
         }
@@ -110,7 +116,8 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.notify_wait_queue();
     }
 
-    fn notify_wait_queue(&self) {
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
         if self.wait_queue_used.load(Ordering::SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -134,6 +141,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
                                                  Ordering::SeqCst) == read_pos {
             // Yo, there was no space and we're the ones who moved the read_pos. Now it's
             // our job to return the data to the sender.
+            self.overwrites.fetch_add(1, Ordering::SeqCst);
             unsafe {
                 Some(ptr::read(self.buf.offset((read_pos & self.cap_mask) as isize)))
             }
@@ -202,6 +210,47 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.have_sleeping.store(false, Ordering::SeqCst);
         rv
     }
+
+    pub fn len(&self) -> usize {
+        // The positions are loaded one after the other so the other endpoint can move
+        // `read_pos` past the `write_pos` we've seen.
+        let (write_pos, read_pos) = self.get_pos();
+        if read_pos > write_pos {
+            return 0;
+        }
+        cmp::min(write_pos - read_pos, self.cap_mask + 1)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap_mask + 1
+    }
+
+    pub fn overwrite_count(&self) -> usize {
+        self.overwrites.load(Ordering::SeqCst)
+    }
+
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        // Like `recv_sync`. All reads go through `recv_async` so that racing with an
+        // overwriting sender is handled by its CAS loop.
+
+        match self.recv_async() {
+            v @ Ok(..) => return v,
+            Err(Error::Empty) => { },
+            e => return e,
+        }
+
+        let mut rv = None;
+        let guard = self.sleeping_mutex.lock().unwrap();
+        self.have_sleeping.store(true, Ordering::SeqCst);
+        let _guard = self.sleeping_condvar.wait_timeout_with(guard, dur, |_| {
+            match self.recv_async() {
+                Err(Error::Empty) => false,
+                v => { rv = Some(v); true },
+            }
+        }).unwrap().0;
+        self.have_sleeping.store(false, Ordering::SeqCst);
+        rv.unwrap_or(Err(Error::Empty))
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
@@ -234,6 +283,14 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         write_pos != read_pos
     }
 
+    fn is_disconnected(&self) -> bool {
+        if !self.sender_disconnected.load(Ordering::SeqCst) {
+            return false;
+        }
+        let (write_pos, read_pos) = self.get_pos();
+        write_pos == read_pos
+    }
+
     fn register(&self, load: Payload<'a>) {
         let mut wait_queue = self.wait_queue.lock().unwrap();
         if wait_queue.add(load) > 0 {
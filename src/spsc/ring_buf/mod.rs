@@ -7,8 +7,11 @@
 //! unconsumed audio samples so that the delay between producer and consumer is bounded
 //! above by the buffer size of the channel.
 
+use std::time::{Duration};
+use std::{fmt};
+
 use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
+use select::{Selectable, RecvSelectable, _Selectable};
 use {Error, Sendable};
 
 mod imp;
@@ -39,6 +42,19 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     pub fn send(&self, val: T) -> Result<Option<T>, (T, Error)> {
         self.data.send(val)
     }
+
+    /// Returns the number of messages in the buffer.
+    ///
+    /// The value is only a snapshot. If the sender is overwriting messages concurrently,
+    /// the value saturates at the capacity of the buffer.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
@@ -73,6 +89,50 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn recv_async(&self) -> Result<T, Error> {
         self.data.recv_async()
     }
+
+    /// Receives a message from the channel. Blocks for at most `dur` if the buffer is
+    /// empty.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The channel is empty and the sender has disconnected.
+    /// - `Empty` - The channel was still empty when the timeout expired.
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, Error> {
+        self.data.recv_timeout(dur)
+    }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns the number of messages in the buffer.
+    ///
+    /// The value is only a snapshot. If the sender is overwriting messages concurrently,
+    /// the value saturates at the capacity of the buffer.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Returns the number of messages the sender has overwritten because the buffer was
+    /// full.
+    pub fn overwrite_count(&self) -> usize {
+        self.data.overwrite_count()
+    }
+
+    /// Returns whether the sender has disconnected and all messages have been received.
+    ///
+    /// Unlike the readiness reported to `Select` objects, this is `false` as long as
+    /// messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.data.is_disconnected()
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
@@ -92,3 +152,29 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::ring_buf::Producer")
+         .field("id", &self.data.unique_id())
+         .field("len", &self.data.len())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::ring_buf::Consumer")
+         .field("id", &self.data.unique_id())
+         .field("len", &self.data.len())
+         .field("capacity", &self.data.capacity())
+         .finish()
+    }
+}
@@ -1,4 +1,5 @@
 use std::thread::{self, sleep_ms};
+use std::time::{Duration};
 
 use select::{Select, Selectable};
 use {Error};
@@ -117,3 +118,68 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn recv_timeout() {
+    let (send, recv) = super::new::<u8>(2);
+    assert_eq!(recv.recv_timeout(Duration::from_millis(5)).unwrap_err(), Error::Empty);
+    send.send(1).unwrap();
+    assert_eq!(recv.recv_timeout(Duration::from_millis(5)).unwrap(), 1);
+    drop(send);
+    assert_eq!(recv.recv_timeout(Duration::from_millis(5)).unwrap_err(),
+               Error::Disconnected);
+}
+
+#[test]
+fn recv_timeout_overwrite() {
+    const NUM: usize = 10000;
+
+    let (send, recv) = super::new(2);
+
+    thread::spawn(move || {
+        for i in 0..NUM {
+            send.send(i).unwrap();
+        }
+    });
+
+    // Messages can be overwritten but the ones we receive must arrive in order.
+    let mut last = None;
+    loop {
+        match recv.recv_timeout(Duration::from_millis(5)) {
+            Ok(n) => {
+                assert!(last.map(|l| l < n).unwrap_or(true));
+                last = Some(n);
+            },
+            Err(Error::Empty) => { },
+            Err(_) => break,
+        }
+    }
+    assert_eq!(last, Some(NUM - 1));
+}
+
+#[test]
+fn len() {
+    let (send, recv) = super::new(2);
+    assert_eq!(send.capacity(), 2);
+    assert_eq!(recv.capacity(), 2);
+    assert_eq!(recv.len(), 0);
+    send.send(1u8).unwrap();
+    assert_eq!(send.len(), 1);
+    send.send(2).unwrap();
+    send.send(3).unwrap();
+    assert_eq!(recv.len(), 2);
+    assert_eq!(recv.recv_async().unwrap(), 2);
+    assert_eq!(send.len(), 1);
+}
+
+#[test]
+fn overwrite_count() {
+    let (send, recv) = super::new(2);
+    send.send(1u8).unwrap();
+    send.send(2).unwrap();
+    assert_eq!(recv.overwrite_count(), 0);
+    assert_eq!(send.send(3).unwrap(), Some(1));
+    assert_eq!(send.send(4).unwrap(), Some(2));
+    assert_eq!(recv.overwrite_count(), 2);
+    assert_eq!(recv.recv_async().unwrap(), 3);
+}
@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicPtr, AtomicBool};
+use std::sync::atomic::{AtomicPtr, AtomicBool, AtomicUsize};
 use std::sync::atomic::Ordering::{SeqCst};
 use std::sync::{Mutex, Condvar};
 use std::{mem, ptr};
@@ -24,6 +24,9 @@ pub struct Packet<'a, T: Sendable+'a> {
     // Has the receiver disconnected?
     receiver_disconnected: AtomicBool,
 
+    // Number of messages in the channel.
+    num_queued: AtomicUsize,
+
     // Is the receiver sleeping?
     have_sleeping: AtomicBool,
     // Mutex to protect the boolean above. XXX: Maybe it doesn't have to be atomic?
@@ -31,6 +34,13 @@ pub struct Packet<'a, T: Sendable+'a> {
     // Condvar the receiver is waiting on.
     sleeping_condvar: Condvar,
 
+    // Is the sender sleeping in `send_bounded`?
+    have_sleeping_sender: AtomicBool,
+    // Mutex to protect the boolean above.
+    sender_mutex: Mutex<()>,
+    // Condvar the sender is waiting on.
+    sender_condvar: Condvar,
+
     // Is someone selecting on this channel?
     wait_queue_used: AtomicBool,
     wait_queue: Mutex<WaitQueue<'a>>,
@@ -66,10 +76,16 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
             sender_disconnected: AtomicBool::new(false),
             receiver_disconnected: AtomicBool::new(false),
 
+            num_queued: AtomicUsize::new(0),
+
             have_sleeping: AtomicBool::new(false),
             sleeping_mutex: Mutex::new(()),
             sleeping_condvar: Condvar::new(),
 
+            have_sleeping_sender: AtomicBool::new(false),
+            sender_mutex: Mutex::new(()),
+            sender_condvar: Condvar::new(),
+
             wait_queue_used: AtomicBool::new(false),
             wait_queue: Mutex::new(WaitQueue::new()),
         }
@@ -86,12 +102,15 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.receiver_disconnected.store(true, SeqCst);
         if !self.sender_disconnected.load(SeqCst) {
             self.notify_sleeping();
+            self.notify_sleeping_sender();
         }
     }
 
-    /// Call this when the sender disconnects.
+    /// Call this when the sender disconnects. Calling this more than once has no effect.
     pub fn disconnect_sender(&self) {
-        self.sender_disconnected.store(true, SeqCst);
+        if self.sender_disconnected.swap(true, SeqCst) {
+            return;
+        }
         if !self.receiver_disconnected.load(SeqCst) {
             self.notify_sleeping();
         }
@@ -106,7 +125,16 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
     }
 
-    fn notify_wait_queue(&self) {
+    /// Wakes up the sender if it's sleeping in `send_bounded`.
+    fn notify_sleeping_sender(&self) {
+        if self.have_sleeping_sender.load(SeqCst) {
+            let _guard = self.sender_mutex.lock().unwrap();
+            self.sender_condvar.notify_one();
+        }
+    }
+
+    /// Notifies everyone selecting on this channel.
+    pub fn notify_wait_queue(&self) {
         if self.wait_queue_used.load(SeqCst) {
             let mut wait_queue = self.wait_queue.lock().unwrap();
             if wait_queue.notify() == 0 {
@@ -116,8 +144,10 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
     }
 
     pub fn send(&self, val: T) -> Result<(), (T, Error)> {
-        // Don't append another message if nobody can receive it.
-        if self.receiver_disconnected.load(SeqCst) {
+        // Don't append another message if nobody can receive it or the sender has been
+        // closed.
+        if self.receiver_disconnected.load(SeqCst)
+                || self.sender_disconnected.load(SeqCst) {
             return Err((val, Error::Disconnected));
         }
 
@@ -133,6 +163,7 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         // our thread sees that the `val` field is None before we set it to anything.
         let write_end = unsafe { &mut *self.write_end.get() };
         write_end.val = Some(val);
+        self.num_queued.fetch_add(1, SeqCst);
         write_end.next.store(new_end, SeqCst);
         self.write_end.set(new_end);
 
@@ -143,6 +174,26 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         Ok(())
     }
 
+    pub fn send_bounded(&self, val: T, max: usize) -> Result<(), (T, Error)> {
+        assert!(max > 0);
+
+        if self.num_queued.load(SeqCst) >= max {
+            let mut guard = self.sender_mutex.lock().unwrap();
+            self.have_sleeping_sender.store(true, SeqCst);
+            // The receiver decrements `num_queued` before it checks
+            // `have_sleeping_sender` so we can't miss a wakeup.
+            while self.num_queued.load(SeqCst) >= max {
+                if self.receiver_disconnected.load(SeqCst) {
+                    break;
+                }
+                guard = self.sender_condvar.wait(guard).unwrap();
+            }
+            self.have_sleeping_sender.store(false, SeqCst);
+        }
+
+        self.send(val)
+    }
+
     pub fn recv_async(&self) -> Result<T, Error> {
         let read_end = unsafe { &mut *self.read_end.load(SeqCst) };
         let next = read_end.next.load(SeqCst);
@@ -155,7 +206,10 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         }
         self.read_end.store(next, SeqCst);
         let mut node = unsafe { mem::transmute::<_, Box<Node<T>>>(read_end) };
-        Ok(node.val.take().unwrap())
+        let val = node.val.take().unwrap();
+        self.num_queued.fetch_sub(1, SeqCst);
+        self.notify_sleeping_sender();
+        Ok(val)
     }
 
     pub fn recv_sync(&self) -> Result<T, Error> {
@@ -179,6 +233,10 @@ impl<'a, T: Sendable+'a> Packet<'a, T> {
         self.have_sleeping.store(false, SeqCst);
         rv
     }
+
+    pub fn is_drained(&self) -> bool {
+        self.sender_disconnected.load(SeqCst) && self.num_queued.load(SeqCst) == 0
+    }
 }
 
 unsafe impl<'a, T: Sendable+'a> Send for Packet<'a, T> { }
@@ -200,6 +258,10 @@ unsafe impl<'a, T: Sendable+'a> _Selectable<'a> for Packet<'a, T> {
         !read_end.next.load(SeqCst).is_null()
     }
 
+    fn is_disconnected(&self) -> bool {
+        self.is_drained()
+    }
+
     fn register(&self, load: Payload<'a>) {
         let mut wait_queue = self.wait_queue.lock().unwrap();
         if wait_queue.add(load) > 0 {
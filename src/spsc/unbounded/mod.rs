@@ -11,8 +11,10 @@
 //! channel the producer will never block and the consumer can start processing the
 //! messages before the producer is finished.
 
+use std::{fmt};
+
 use arc::{Arc, ArcTrait};
-use select::{Selectable, _Selectable};
+use select::{Selectable, RecvSelectable, _Selectable};
 use {Error, Sendable};
 
 mod imp;
@@ -26,6 +28,25 @@ pub fn new<'a, T: Sendable+'a>() -> (Producer<'a, T>, Consumer<'a, T>) {
     (Producer { data: packet.clone() }, Consumer { data: packet })
 }
 
+/// Creates an unbounded SPSC channel whose sender has already disconnected.
+///
+/// This is mostly useful for testing code that consumes messages.
+pub fn new_disconnected<'a, T: Sendable+'a>() -> Consumer<'a, T> {
+    new_with_values(vec!())
+}
+
+/// Creates an unbounded SPSC channel that contains the messages `vals` and whose sender
+/// has already disconnected.
+///
+/// This is mostly useful for testing code that consumes messages.
+pub fn new_with_values<'a, T: Sendable+'a>(vals: Vec<T>) -> Consumer<'a, T> {
+    let (send, recv) = new();
+    for val in vals {
+        assert!(send.send(val).is_ok());
+    }
+    recv
+}
+
 /// The producing half on an unbounded SPSC channel.
 pub struct Producer<'a, T: Sendable+'a> {
     data: Arc<imp::Packet<'a, T>>,
@@ -36,10 +57,36 @@ impl<'a, T: Sendable+'a> Producer<'a, T> {
     ///
     /// ### Error
     ///
-    /// - `Disconnected` - The receiver has disconnected.
+    /// - `Disconnected` - The receiver has disconnected or the producer has been closed.
     pub fn send(&self, val: T) -> Result<(), (T, Error)> {
         self.data.send(val)
     }
+
+    /// Appends a new message to the channel. Blocks while `max` or more messages are
+    /// queued in the channel.
+    ///
+    /// This can be used to limit the memory used by the channel if the receiver can't
+    /// keep up.
+    ///
+    /// ### Error
+    ///
+    /// - `Disconnected` - The receiver has disconnected or the producer has been closed.
+    ///
+    /// ### Panic
+    ///
+    /// Panics if `max` is `0`.
+    pub fn send_bounded(&self, val: T, max: usize) -> Result<(), (T, Error)> {
+        self.data.send_bounded(val, max)
+    }
+
+    /// Disconnects the producer as if it had been dropped.
+    ///
+    /// Afterwards all attempts to send fail with `Disconnected` and the consumer sees the
+    /// channel as disconnected after it has received the remaining messages. Closing the
+    /// producer more than once has no effect.
+    pub fn close(&self) {
+        self.data.disconnect_sender();
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Producer<'a, T> {
@@ -74,6 +121,40 @@ impl<'a, T: Sendable+'a> Consumer<'a, T> {
     pub fn recv_async(&self) -> Result<T, Error> {
         self.data.recv_async()
     }
+
+    /// Wakes all `Select` objects this channel is registered with so that they check the
+    /// readiness of the channel again. The state of the channel is not modified.
+    pub fn poke_selects(&self) {
+        self.data.notify_wait_queue();
+    }
+
+    /// Returns whether the sender has disconnected and all messages have been
+    /// received.
+    ///
+    /// If this returns `true`, every further receive operation returns `Disconnected`.
+    pub fn is_drained(&self) -> bool {
+        self.data.is_drained()
+    }
+
+    /// Returns whether the sender has disconnected and all messages have been received.
+    ///
+    /// This is the same as `is_drained`. Unlike the readiness reported to `Select`
+    /// objects, this is `false` as long as messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.is_drained()
+    }
+
+    /// Returns the number of strong references to the channel's shared state, including
+    /// the one held by this consumer.
+    ///
+    /// The state is freed once all of these references are gone. `Select` objects only
+    /// hold weak references to registered consumers, so a registration doesn't count
+    /// towards this number except while a `Select` is checking the consumer's
+    /// readiness. The result is only a snapshot and might be outdated by the time it's
+    /// returned.
+    pub fn strong_refs(&self) -> usize {
+        self.data.strong_count()
+    }
 }
 
 impl<'a, T: Sendable+'a> Drop for Consumer<'a, T> {
@@ -93,3 +174,25 @@ impl<'a, T: Sendable+'a> Selectable<'a> for Consumer<'a, T> {
         unsafe { self.data.as_trait(&*self.data as &(_Selectable+'a)) }
     }
 }
+
+impl<'a, T: Sendable+'a> RecvSelectable<'a, T> for Consumer<'a, T> {
+    fn recv_async(&self) -> Result<T, Error> {
+        Consumer::recv_async(self)
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Producer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::unbounded::Producer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
+
+impl<'a, T: Sendable+'a> fmt::Debug for Consumer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("spsc::unbounded::Consumer")
+         .field("id", &self.data.unique_id())
+         .finish()
+    }
+}
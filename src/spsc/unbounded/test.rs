@@ -1,4 +1,6 @@
 use std::thread::{self, sleep_ms};
+use std::sync::atomic::{AtomicBool};
+use std::sync::atomic::Ordering::{SeqCst};
 
 use select::{Select, Selectable};
 use {Error};
@@ -84,6 +86,43 @@ fn send_5_recv_5() {
     assert_eq!(recv.recv_async().unwrap_err(), Error::Empty);
 }
 
+#[test]
+fn send_bounded() {
+    let (send, recv) = super::new();
+    let sent = AtomicBool::new(false);
+
+    let t = {
+        let sent = &sent;
+        thread::scoped(move || {
+            send.send_bounded(1u8, 2).unwrap();
+            send.send_bounded(2u8, 2).unwrap();
+            send.send_bounded(3u8, 2).unwrap();
+            sent.store(true, SeqCst);
+            send.send_bounded(4u8, 2).unwrap();
+        })
+    };
+
+    ms_sleep(100);
+    assert!(!sent.load(SeqCst));
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+    assert_eq!(recv.recv_sync().unwrap(), 3);
+    assert_eq!(recv.recv_sync().unwrap(), 4);
+    drop(t);
+    assert!(sent.load(SeqCst));
+}
+
+#[test]
+fn send_bounded_disconnect() {
+    let (send, recv) = super::new();
+    send.send_bounded(1u8, 1).unwrap();
+    thread::spawn(move || {
+        ms_sleep(100);
+        drop(recv);
+    });
+    assert_eq!(send.send_bounded(2u8, 1).unwrap_err(), (2, Error::Disconnected));
+}
+
 #[test]
 fn select_no_wait() {
     let (send, recv) = super::new();
@@ -116,3 +155,71 @@ fn select_wait() {
 
     assert_eq!(buf[0], recv.id());
 }
+
+#[test]
+fn is_drained() {
+    let (send, recv) = super::new();
+    send.send(1u8).unwrap();
+    send.send(2u8).unwrap();
+    assert!(!recv.is_drained());
+    drop(send);
+    assert!(!recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap(), 1);
+    assert!(!recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap(), 2);
+    assert!(recv.is_drained());
+    assert_eq!(recv.recv_async().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn new_disconnected() {
+    let recv = super::new_disconnected::<u8>();
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn new_with_values() {
+    let recv = super::new_with_values(vec!(1u8, 2, 3));
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap(), 2);
+    assert_eq!(recv.recv_sync().unwrap(), 3);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+}
+
+#[test]
+fn close() {
+    let (send, recv) = super::new();
+    send.send(1u8).unwrap();
+    send.close();
+    assert_eq!(send.send(2).unwrap_err(), (2, Error::Disconnected));
+    send.close();
+    assert_eq!(recv.recv_sync().unwrap(), 1);
+    assert_eq!(recv.recv_sync().unwrap_err(), Error::Disconnected);
+    drop(send);
+}
+
+#[test]
+fn boxed_closures() {
+    let (send, recv) = super::new::<Box<Fn() -> u8 + Send>>();
+    let _t = thread::scoped(move || {
+        for i in 0..3 {
+            send.send(Box::new(move || i)).ok().unwrap();
+        }
+    });
+    for i in 0..3 {
+        assert_eq!((recv.recv_sync().unwrap())(), i);
+    }
+}
+
+#[test]
+fn strong_refs() {
+    let (send, recv) = super::new::<u8>();
+    assert_eq!(recv.strong_refs(), 2);
+
+    let select = Select::new();
+    select.add(&recv);
+    assert_eq!(recv.strong_refs(), 2);
+    select.remove(&recv);
+    drop(send);
+    assert_eq!(recv.strong_refs(), 1);
+}